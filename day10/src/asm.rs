@@ -0,0 +1,152 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::{MachineOp, Operand, Register};
+
+/// An assembled program along with the addresses its labels point to.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Program {
+    pub ops: Vec<MachineOp>,
+    pub labels: BTreeMap<String, usize>,
+}
+
+type Arithmetic = fn(Register, Operand) -> MachineOp;
+
+/// Prefixes of the opcodes that get followed by the register they write to.
+const ARITHMETIC: [(&str, Arithmetic); 3] = [
+    ("add", MachineOp::Add),
+    ("sub", MachineOp::Sub),
+    ("mul", MachineOp::Mul),
+];
+
+fn parse_register(opcode: &str, s: Option<&str>) -> Result<Register, String> {
+    let s = s.ok_or(format!("Missing {} register", opcode))?;
+    Register::parse(s).ok_or(format!("Unknown register: {}", s))
+}
+
+fn parse_operand(opcode: &str, s: Option<&str>) -> Result<Operand, String> {
+    let s = s.ok_or(format!("Missing {} operand", opcode))?;
+    match Register::parse(s) {
+        Some(r) => Ok(Operand::Reg(r)),
+        None => s
+            .parse::<i32>()
+            .map(Operand::Imm)
+            .map_err(|_| format!("Couldn't parse {} operand: {}", opcode, s)),
+    }
+}
+
+/// Parses a jump target, which is either an offset or a label.
+fn parse_offset(
+    opcode: &str,
+    s: Option<&str>,
+    ip: usize,
+    labels: &BTreeMap<String, usize>,
+) -> Result<i32, String> {
+    let s = s.ok_or(format!("Missing {} target", opcode))?;
+    if is_label(s) {
+        let addr = labels.get(s).ok_or(format!("Unknown label: {}", s))?;
+        Ok(*addr as i32 - ip as i32)
+    } else {
+        s.parse::<i32>()
+            .map_err(|_| format!("Couldn't parse {} target: {}", opcode, s))
+    }
+}
+
+/// Parses a single instruction at address `ip` without any label or
+/// comment, resolving jump targets through `labels`.
+///
+/// Arithmetic opcodes name the register they write to, like `addx` or
+/// `muly`, and take either an immediate or another register.
+pub fn parse_instruction(
+    s: &str,
+    ip: usize,
+    labels: &BTreeMap<String, usize>,
+) -> Result<MachineOp, String> {
+    let mut parts = s.split_whitespace();
+    let opcode = parts.next().ok_or("Missing opcode".to_string())?;
+    let op = match opcode {
+        "noop" => MachineOp::NoOp,
+        "halt" => MachineOp::Halt,
+        "jmp" => MachineOp::Jump(parse_offset(opcode, parts.next(), ip, labels)?),
+        "jz" | "jnz" => {
+            let r = parse_register(opcode, parts.next())?;
+            let offset = parse_offset(opcode, parts.next(), ip, labels)?;
+            if opcode == "jz" {
+                MachineOp::JumpIfZero(r, offset)
+            } else {
+                MachineOp::JumpIfNotZero(r, offset)
+            }
+        }
+        _ => {
+            let (op, r) = ARITHMETIC
+                .into_iter()
+                .find_map(|(name, op)| Some((op, Register::parse(opcode.strip_prefix(name)?)?)))
+                .ok_or(format!("Unknown opcode: {}", opcode))?;
+            op(r, parse_operand(opcode, parts.next())?)
+        }
+    };
+    match parts.next() {
+        Some(operand) => Err(format!("Unexpected operand: {}", operand)),
+        None => Ok(op),
+    }
+}
+
+fn is_label(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Assembles a program with one instruction per line.
+///
+/// Everything after a `;` is a comment and blank lines are skipped. A line
+/// may start with a `label:`, which refers to the address of the next
+/// instruction.
+pub fn assemble(source: &str) -> Result<Program, String> {
+    let mut program = Program::default();
+    let mut instructions = vec![];
+    for (i, line) in source.lines().enumerate() {
+        let line = line.split(';').next().unwrap_or("").trim();
+        let (label, instruction) = match line.split_once(':') {
+            Some((label, instruction)) => (Some(label.trim()), instruction.trim()),
+            None => (None, line),
+        };
+        if let Some(label) = label {
+            if !is_label(label) {
+                return Err(format!("Line {}: Invalid label: {}", i + 1, label));
+            }
+            if program
+                .labels
+                .insert(label.to_string(), instructions.len())
+                .is_some()
+            {
+                return Err(format!("Line {}: Duplicate label: {}", i + 1, label));
+            }
+        }
+        if !instruction.is_empty() {
+            instructions.push((i + 1, instruction));
+        }
+    }
+    // Labels may be used before they're defined, so instructions are only
+    // parsed once all of them are known.
+    for (ip, (line, instruction)) in instructions.into_iter().enumerate() {
+        let op = parse_instruction(instruction, ip, &program.labels)
+            .map_err(|e| format!("Line {}: {}", line, e))?;
+        program.ops.push(op);
+    }
+    Ok(program)
+}
+
+/// Turns a program back into source that assembles to the same program,
+/// with every label on its own line in front of the instruction it marks.
+pub fn disassemble(program: &Program) -> String {
+    let mut ret = String::new();
+    for ip in 0..=program.ops.len() {
+        for (label, _) in program.labels.iter().filter(|(_, addr)| **addr == ip) {
+            writeln!(ret, "{}:", label).unwrap();
+        }
+        if let Some(op) = program.ops.get(ip) {
+            writeln!(ret, "    {}", op).unwrap();
+        }
+    }
+    ret
+}
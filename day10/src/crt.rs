@@ -0,0 +1,157 @@
+use std::fmt;
+
+/// The cycles during which the signal strength gets sampled.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SampleSchedule {
+    /// Samples during cycle `first` and every `interval` cycles after it.
+    Every {
+        first: usize,
+        interval: usize,
+    },
+    Cycles(Vec<usize>),
+}
+
+impl SampleSchedule {
+    /// Parses either `first+interval` or a comma separated list of cycles.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let number = |n: &str| {
+            n.trim()
+                .parse::<usize>()
+                .map_err(|_| format!("Invalid cycle: {}", n))
+        };
+        match s.split_once('+') {
+            Some((first, interval)) => match number(interval)? {
+                0 => Err("The sample interval can't be 0".to_string()),
+                interval => Ok(Self::Every {
+                    first: number(first)?,
+                    interval,
+                }),
+            },
+            None => s
+                .split(',')
+                .map(number)
+                .collect::<Result<_, _>>()
+                .map(Self::Cycles),
+        }
+    }
+
+    pub fn contains(&self, cycle: usize) -> bool {
+        match self {
+            Self::Every { first, interval } => {
+                cycle >= *first && (cycle - first).is_multiple_of(*interval)
+            }
+            Self::Cycles(cycles) => cycles.contains(&cycle),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CrtConfig {
+    pub width: usize,
+    pub height: usize,
+    /// Number of pixels the sprite covers, centered on `x`.
+    pub sprite_width: usize,
+    pub samples: SampleSchedule,
+}
+
+impl Default for CrtConfig {
+    fn default() -> Self {
+        Self {
+            width: 40,
+            height: 6,
+            sprite_width: 3,
+            samples: SampleSchedule::Every {
+                first: 20,
+                interval: 40,
+            },
+        }
+    }
+}
+
+impl CrtConfig {
+    /// Whether the sprite at `x` covers the given column. Sprites of even
+    /// width reach one pixel further to the right than to the left.
+    pub fn sprite_covers(&self, x: i32, column: usize) -> bool {
+        let start = x as i64 - (self.sprite_width as i64 - 1) / 2;
+        (start..start + self.sprite_width as i64).contains(&(column as i64))
+    }
+}
+
+/// The pixels of a single frame, stored row by row.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<bool>,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![false; width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, on: bool) {
+        self.pixels[y * self.width + x] = on;
+    }
+
+    pub fn clear(&mut self) {
+        self.pixels.fill(false);
+    }
+
+    pub fn row(&self, y: usize, on: char, off: char) -> String {
+        self.pixels[y * self.width..(y + 1) * self.width]
+            .iter()
+            .map(|p| if *p { on } else { off })
+            .collect()
+    }
+
+    /// Renders the frame with one line per row.
+    pub fn render(&self, on: char, off: char) -> String {
+        (0..self.height)
+            .map(|y| self.row(y, on, off))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Renders the frame as a plain PBM image, scaling every pixel up to
+    /// a square of `scale` pixels.
+    pub fn to_pbm(&self, scale: usize) -> String {
+        let mut ret = format!("P1\n{} {}\n", self.width * scale, self.height * scale);
+        for y in 0..self.height * scale {
+            let row = (0..self.width * scale)
+                .map(|x| {
+                    if self.get(x / scale, y / scale) {
+                        "1"
+                    } else {
+                        "0"
+                    }
+                })
+                .collect::<Vec<_>>();
+            ret.push_str(&row.join(" "));
+            ret.push('\n');
+        }
+        ret
+    }
+}
+
+impl fmt::Display for Framebuffer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.render('#', '.'))
+    }
+}
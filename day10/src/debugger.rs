@@ -0,0 +1,284 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+use crate::asm::Program;
+use crate::{Machine, Register, MAX_CYCLES};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// Stops right before the given cycle starts.
+    Cycle(usize),
+    /// Stops right before the instruction at the given address starts.
+    Ip(usize),
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Cycle(cycle) => write!(f, "cycle {}", cycle),
+            Self::Ip(ip) => write!(f, "ip {}", ip),
+        }
+    }
+}
+
+/// Why the debugger handed control back.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Stop {
+    Stepped,
+    Breakpoint(Breakpoint),
+    Watch {
+        reg: Register,
+        old: i32,
+        new: i32,
+    },
+    Halted,
+    /// Ran for the given number of cycles without anything else stopping
+    /// the machine.
+    CycleLimit(usize),
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Stepped => write!(f, "Stepped"),
+            Self::Breakpoint(b) => write!(f, "Breakpoint at {}", b),
+            Self::Watch { reg, old, new } => {
+                write!(f, "Watched {} changed from {} to {}", reg.name(), old, new)
+            }
+            Self::Halted => write!(f, "Halted"),
+            Self::CycleLimit(cycles) => write!(f, "Still running after {} cycles", cycles),
+        }
+    }
+}
+
+pub struct Debugger {
+    machine: Machine,
+    labels: BTreeMap<String, usize>,
+    breakpoints: Vec<Breakpoint>,
+    watches: Vec<Register>,
+}
+
+impl Debugger {
+    pub fn new(program: Program) -> Self {
+        Self {
+            machine: Machine::new(program.ops),
+            labels: program.labels,
+            breakpoints: vec![],
+            watches: vec![],
+        }
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        if !self.breakpoints.contains(&breakpoint) {
+            self.breakpoints.push(breakpoint);
+        }
+    }
+
+    pub fn remove_breakpoint(&mut self, breakpoint: Breakpoint) -> bool {
+        let len = self.breakpoints.len();
+        self.breakpoints.retain(|b| *b != breakpoint);
+        self.breakpoints.len() != len
+    }
+
+    /// Stops whenever the value of `reg` changes.
+    pub fn watch(&mut self, reg: Register) {
+        if !self.watches.contains(&reg) {
+            self.watches.push(reg);
+        }
+    }
+
+    pub fn unwatch(&mut self, reg: Register) {
+        self.watches.retain(|r| *r != reg);
+    }
+
+    fn hit_breakpoint(&self) -> Option<Breakpoint> {
+        let m = &self.machine;
+        self.breakpoints.iter().copied().find(|b| match b {
+            Breakpoint::Cycle(cycle) => m.cycles + 1 == *cycle,
+            Breakpoint::Ip(ip) => m.ip == *ip && m.wait_cycles == 0,
+        })
+    }
+
+    /// Runs a single cycle.
+    pub fn step_cycle(&mut self) -> Stop {
+        if !self.machine.is_running() {
+            return Stop::Halted;
+        }
+        let before = self.machine.regs;
+        self.machine.step();
+        for reg in self.watches.iter() {
+            let (old, new) = (before[*reg as usize], self.machine.reg(*reg));
+            if old != new {
+                return Stop::Watch {
+                    reg: *reg,
+                    old,
+                    new,
+                };
+            }
+        }
+        match self.hit_breakpoint() {
+            Some(b) => Stop::Breakpoint(b),
+            None if self.machine.is_running() => Stop::Stepped,
+            None => Stop::Halted,
+        }
+    }
+
+    /// Runs until the current instruction has finished, or something else
+    /// stops the machine first.
+    pub fn step_instruction(&mut self) -> Stop {
+        loop {
+            let stop = self.step_cycle();
+            if stop != Stop::Stepped || self.machine.wait_cycles == 0 {
+                return stop;
+            }
+        }
+    }
+
+    /// Runs until something stops the machine, or hands control back after
+    /// `MAX_CYCLES` cycles so that endless loops can be interrupted.
+    pub fn resume(&mut self) -> Stop {
+        let start = self.machine.cycles;
+        loop {
+            let stop = self.step_cycle();
+            if stop != Stop::Stepped {
+                return stop;
+            }
+            if self.machine.cycles - start >= MAX_CYCLES {
+                return Stop::CycleLimit(MAX_CYCLES);
+            }
+        }
+    }
+
+    /// The CRT row the beam is currently in, with `_` for pixels that
+    /// haven't been drawn yet.
+    pub fn crt_row(&self) -> String {
+        let (frame, beam) = (&self.machine.frame, self.machine.beam);
+        let y = beam.saturating_sub(1) / frame.width();
+        let drawn = beam - y * frame.width();
+        let mut row = frame.row(y, '#', '.');
+        row.replace_range(drawn.., &"_".repeat(frame.width() - drawn));
+        row
+    }
+
+    /// Registers and position of the machine.
+    pub fn status(&self) -> String {
+        let m = &self.machine;
+        let op = match m.mem.get(m.ip) {
+            Some(op) if m.is_running() => op.to_string(),
+            _ => "halted".to_string(),
+        };
+        let regs = Register::ALL
+            .iter()
+            .map(|r| format!("{}={}", r.name(), m.reg(*r)))
+            .collect::<Vec<_>>();
+        format!(
+            "cycle {} ip {} ({}, waited {}) {}",
+            m.cycles + 1,
+            m.ip,
+            op,
+            m.wait_cycles,
+            regs.join(" ")
+        )
+    }
+
+    fn parse_breakpoint(&self, args: &[&str]) -> Result<Breakpoint, String> {
+        match args {
+            ["cycle", n] => n
+                .parse()
+                .map(Breakpoint::Cycle)
+                .map_err(|_| format!("Invalid cycle: {}", n)),
+            ["ip", n] => match self.labels.get(*n) {
+                Some(ip) => Ok(Breakpoint::Ip(*ip)),
+                None => n
+                    .parse()
+                    .map(Breakpoint::Ip)
+                    .map_err(|_| format!("Invalid ip or unknown label: {}", n)),
+            },
+            _ => Err("Expected 'cycle N' or 'ip N'".to_string()),
+        }
+    }
+
+    fn repeat(&mut self, count: &str, step: fn(&mut Self) -> Stop) -> Result<Stop, String> {
+        let count = count
+            .parse::<usize>()
+            .map_err(|_| format!("Invalid count: {}", count))?;
+        let start = self.machine.cycles;
+        let mut stop = Stop::Stepped;
+        for _ in 0..count {
+            stop = step(self);
+            if stop != Stop::Stepped {
+                break;
+            }
+            if self.machine.cycles - start >= MAX_CYCLES {
+                return Ok(Stop::CycleLimit(MAX_CYCLES));
+            }
+        }
+        Ok(stop)
+    }
+
+    /// Executes a single debugger command and returns what to print.
+    ///
+    /// `break` and `delete` take `cycle N` or `ip N`, where the address may
+    /// also be a label. `step` runs cycles and `next` whole instructions,
+    /// both optionally followed by a count.
+    pub fn execute(&mut self, command: &str) -> Result<String, String> {
+        let words = command.split_whitespace().collect::<Vec<_>>();
+        let stop = match words.as_slice() {
+            ["break", args @ ..] => {
+                let b = self.parse_breakpoint(args)?;
+                self.add_breakpoint(b);
+                return Ok(format!("Breakpoint at {}", b));
+            }
+            ["delete", args @ ..] => {
+                let b = self.parse_breakpoint(args)?;
+                return match self.remove_breakpoint(b) {
+                    true => Ok(format!("Deleted breakpoint at {}", b)),
+                    false => Err(format!("No breakpoint at {}", b)),
+                };
+            }
+            ["watch", r] | ["unwatch", r] => {
+                let reg = Register::parse(r).ok_or(format!("Unknown register: {}", r))?;
+                if words[0] == "watch" {
+                    self.watch(reg);
+                    return Ok(format!("Watching {}", r));
+                }
+                self.unwatch(reg);
+                return Ok(format!("Stopped watching {}", r));
+            }
+            ["step"] => self.step_cycle(),
+            ["step", n] => self.repeat(n, Self::step_cycle)?,
+            ["next"] => self.step_instruction(),
+            ["next", n] => self.repeat(n, Self::step_instruction)?,
+            ["continue"] => self.resume(),
+            ["print"] => return Ok(self.status()),
+            ["crt"] => return Ok(self.crt_row()),
+            _ => return Err(format!("Unknown command: {}", command.trim())),
+        };
+        Ok(match stop {
+            Stop::Stepped => self.status(),
+            stop => format!("{}\n{}", stop, self.status()),
+        })
+    }
+
+    /// Reads commands until the input ends or `quit` is entered.
+    pub fn repl(&mut self, input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+        writeln!(output, "{}", self.status())?;
+        write!(output, "> ")?;
+        output.flush()?;
+        for line in input.lines() {
+            let line = line?;
+            match line.trim() {
+                "quit" => break,
+                "" => (),
+                command => match self.execute(command) {
+                    Ok(s) => writeln!(output, "{}", s)?,
+                    Err(e) => writeln!(output, "Error: {}", e)?,
+                },
+            }
+            write!(output, "> ")?;
+            output.flush()?;
+        }
+        Ok(())
+    }
+}
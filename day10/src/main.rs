@@ -1,52 +1,132 @@
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+mod asm;
+mod crt;
+mod debugger;
+mod ocr;
+mod trace;
 
-#[derive(Copy, Clone, Debug)]
+use std::fmt;
+use std::fs;
+use std::io;
+
+use asm::Program;
+use crt::{CrtConfig, Framebuffer, SampleSchedule};
+use debugger::Debugger;
+use trace::TraceEntry;
+
+/// Programs still running after this many cycles are assumed to loop
+/// forever.
+const MAX_CYCLES: usize = 1 << 20;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Register {
+    X,
+    Y,
+    Z,
+    W,
+}
+
+impl Register {
+    pub const ALL: [Register; 4] = [Self::X, Self::Y, Self::Z, Self::W];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::X => "x",
+            Self::Y => "y",
+            Self::Z => "z",
+            Self::W => "w",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|r| r.name() == s)
+    }
+}
+
+/// The value an arithmetic instruction operates with.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Operand {
+    Imm(i32),
+    Reg(Register),
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Imm(imm) => write!(f, "{}", imm),
+            Self::Reg(r) => write!(f, "{}", r.name()),
+        }
+    }
+}
+
+/// Jump offsets are relative to the jump instruction itself.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 enum MachineOp {
     NoOp,
-    AddX(i32),
+    Add(Register, Operand),
+    Sub(Register, Operand),
+    Mul(Register, Operand),
+    Jump(i32),
+    JumpIfZero(Register, i32),
+    JumpIfNotZero(Register, i32),
+    Halt,
 }
 
 impl TryFrom<String> for MachineOp {
     type Error = String;
     fn try_from(s: String) -> Result<Self, Self::Error> {
-        let mut parts = s.split(' ');
-        let opcode = parts.next().ok_or("Missing opcode".to_string())?;
-        match opcode {
-            "noop" => return Ok(MachineOp::NoOp),
-            "addx" => {
-                let imm = parts.next().ok_or("Missing addx immediate".to_string())?;
-                let imm = imm.parse::<i32>();
-                if let Ok(imm) = imm {
-                    return Ok(MachineOp::AddX(imm));
-                } else {
-                    return Err("Couldn't parse addx immediate".to_string());
-                }
-            },
-            _ => return Err(format!("Unknown upcode: {}", opcode))
+        asm::parse_instruction(&s, 0, &Default::default())
+    }
+}
+
+impl fmt::Display for MachineOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NoOp => write!(f, "noop"),
+            Self::Add(r, v) => write!(f, "add{} {}", r.name(), v),
+            Self::Sub(r, v) => write!(f, "sub{} {}", r.name(), v),
+            Self::Mul(r, v) => write!(f, "mul{} {}", r.name(), v),
+            Self::Jump(offset) => write!(f, "jmp {}", offset),
+            Self::JumpIfZero(r, offset) => write!(f, "jz {} {}", r.name(), offset),
+            Self::JumpIfNotZero(r, offset) => write!(f, "jnz {} {}", r.name(), offset),
+            Self::Halt => write!(f, "halt"),
         }
     }
 }
 
 impl MachineOp {
+    /// Number of cycles an instruction takes on top of the first one.
     pub fn cycles(&self) -> usize {
         match self {
             Self::NoOp => 0,
-            Self::AddX(_) => 1,
+            Self::Add(..) => 1,
+            Self::Sub(..) => 1,
+            Self::Mul(..) => 3,
+            Self::Jump(_) => 1,
+            Self::JumpIfZero(..) => 1,
+            Self::JumpIfNotZero(..) => 1,
+            Self::Halt => 0,
         }
     }
 
     pub fn eval(&self, machine: &mut Machine) {
         if machine.wait_cycles >= self.cycles() {
             machine.wait_cycles = 0;
-            match self {
-                Self::NoOp => machine.ip += 1,
-                Self::AddX(imm) => {
-                    machine.x += imm;
-                    machine.ip += 1;
-                },
+            let mut offset = 1;
+            match *self {
+                Self::NoOp => (),
+                Self::Add(r, v) => machine.set(r, machine.reg(r).wrapping_add(machine.value(v))),
+                Self::Sub(r, v) => machine.set(r, machine.reg(r).wrapping_sub(machine.value(v))),
+                Self::Mul(r, v) => machine.set(r, machine.reg(r).wrapping_mul(machine.value(v))),
+                Self::Jump(o) => offset = o,
+                Self::JumpIfZero(r, o) if machine.reg(r) == 0 => offset = o,
+                Self::JumpIfNotZero(r, o) if machine.reg(r) != 0 => offset = o,
+                Self::JumpIfZero(..) | Self::JumpIfNotZero(..) => (),
+                Self::Halt => {
+                    machine.halted = true;
+                    offset = 0;
+                }
             };
-            return;
+            machine.jump(offset);
         } else {
             machine.wait_cycles += 1;
         }
@@ -55,63 +135,157 @@ impl MachineOp {
 
 #[derive(Clone, Debug)]
 struct Machine {
-    x: i32,
+    regs: [i32; Register::ALL.len()],
     ip: usize,
     cycles: usize,
     wait_cycles: usize,
+    halted: bool,
     signal_strength: isize,
     mem: Vec<MachineOp>,
 
-    crt: Vec<char>,
+    config: CrtConfig,
+    frame: Framebuffer,
+    /// Index of the next pixel to draw within the frame.
+    beam: usize,
+    trace: Option<Vec<TraceEntry>>,
 }
 
 impl Machine {
     pub fn new(mem: Vec<MachineOp>) -> Self {
+        Self::with_config(mem, CrtConfig::default())
+    }
+
+    pub fn with_config(mem: Vec<MachineOp>, config: CrtConfig) -> Self {
+        assert!(
+            config.width > 0 && config.height > 0,
+            "The CRT needs at least one pixel"
+        );
         Self {
-            x: 1,
+            regs: [1, 0, 0, 0],
             ip: 0,
             cycles: 0,
+            halted: false,
             signal_strength: 0,
             wait_cycles: 0,
             mem,
-            crt: vec![]
+            frame: Framebuffer::new(config.width, config.height),
+            config,
+            beam: 0,
+            trace: None,
+        }
+    }
+
+    /// Records what happens during every cycle from now on.
+    pub fn traced(mut self) -> Self {
+        self.trace.get_or_insert_with(Vec::new);
+        self
+    }
+
+    pub fn trace(&self) -> Option<&[TraceEntry]> {
+        self.trace.as_deref()
+    }
+
+    pub fn reg(&self, r: Register) -> i32 {
+        self.regs[r as usize]
+    }
+
+    fn set(&mut self, r: Register, value: i32) {
+        self.regs[r as usize] = value;
+    }
+
+    pub fn x(&self) -> i32 {
+        self.reg(Register::X)
+    }
+
+    fn value(&self, v: Operand) -> i32 {
+        match v {
+            Operand::Imm(imm) => imm,
+            Operand::Reg(r) => self.reg(r),
+        }
+    }
+
+    /// Moves the instruction pointer, jumping before the start of the
+    /// program halts the machine just like running past its end does.
+    fn jump(&mut self, offset: i32) {
+        match self.ip.checked_add_signed(offset as isize) {
+            Some(ip) => self.ip = ip,
+            None => self.halted = true,
         }
     }
 
     fn update_signal_strength(&mut self) {
-        if (self.cycles + 20) % 40 == 0 {
-            self.signal_strength += self.cycles as isize * self.x as isize;
+        if self.config.samples.contains(self.cycles) {
+            self.signal_strength += self.cycles as isize * self.x() as isize;
         }
     }
 
-    fn update_crt(&mut self) {
-        let crt_x = (self.cycles % 40) as isize;
-        let low = (self.x) as isize;
-        let high = (self.x+2) as isize;
-        if crt_x >= low && crt_x <= high {
-            self.crt.push('#');
-        } else {
-            self.crt.push('.');
+    /// Draws the next pixel, starting over with a blank frame once the
+    /// previous one is complete.
+    fn update_crt(&mut self) -> bool {
+        let width = self.config.width;
+        if self.beam == self.frame.width() * self.frame.height() {
+            self.beam = 0;
+            self.frame.clear();
         }
+        let (column, row) = (self.beam % width, self.beam / width);
+        let on = self.config.sprite_covers(self.x(), column);
+        self.frame.set(column, row, on);
+        self.beam += 1;
+        on
     }
 
     pub fn print_crt(&self) {
         println!("+++ Crt +++");
-        self.crt.chunks(40).for_each(|line| {
-            line.iter().for_each(|c| print!("{}",c));
-            println!("");
-        });
+        println!("{}", self.frame);
         println!("--- End of Crt ---");
     }
 
-    pub fn run(&mut self) {
-        while self.ip < self.mem.len() {
-            self.cycles += 1;
-            self.update_signal_strength();
-            self.update_crt();
-            let op = self.mem[self.ip];
-            op.eval(self);
+    pub fn is_running(&self) -> bool {
+        !self.halted && self.ip < self.mem.len()
+    }
+
+    /// Runs a single cycle, which only finishes an instruction once it
+    /// has waited for all of its cycles.
+    pub fn step(&mut self) {
+        let (ip, waited, x_before) = (self.ip, self.wait_cycles, self.x());
+        self.cycles += 1;
+        self.update_signal_strength();
+        let pixel = self.update_crt();
+        let op = self.mem[self.ip];
+        op.eval(self);
+        let x_after = self.x();
+        if let Some(trace) = self.trace.as_mut() {
+            trace.push(TraceEntry {
+                cycle: self.cycles,
+                ip,
+                op,
+                waited,
+                x_before,
+                x_after,
+                pixel,
+            });
+        }
+    }
+
+    /// Reads the letters drawn on the CRT.
+    pub fn read_crt(&self) -> Result<String, String> {
+        ocr::recognize(&self.frame)
+    }
+
+    /// Runs until the program halts, or fails once the machine has run for
+    /// `max_cycles` cycles in total without halting.
+    pub fn run_for(&mut self, max_cycles: usize) -> Result<(), String> {
+        while self.is_running() {
+            if self.cycles >= max_cycles {
+                return Err(format!("Still running after {} cycles", max_cycles));
+            }
+            self.step();
         }
+        Ok(())
+    }
+
+    pub fn run(&mut self) -> Result<(), String> {
+        self.run_for(MAX_CYCLES)
     }
 
     pub fn signal_strength(&self) -> isize {
@@ -119,28 +293,98 @@ impl Machine {
     }
 }
 
-fn run(path: &str) -> isize {
-    let file = File::open(path).unwrap();
+fn read_program(path: &str) -> Result<Program, String> {
+    let source = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    asm::assemble(&source).map_err(|e| format!("{}: {}", path, e))
+}
 
+fn run(path: &str) -> Result<Machine, String> {
+    let mut machine = Machine::new(read_program(path)?.ops);
+    machine.run()?;
+    Ok(machine)
+}
 
-    let program:Vec<MachineOp> = BufReader::new(file)
-        .lines()
-        .map(|line| {
-            let line = line.unwrap();
-            line.try_into().unwrap()
-        }).collect();
-    let mut machine = Machine::new(program);
-    machine.run();
-    machine.print_crt();
-    machine.signal_strength()
+fn run_both(path: &str) -> Result<(isize, String), String> {
+    let machine = run(path)?;
+    Ok((machine.signal_strength(), machine.read_crt()?))
 }
 
 fn main() {
-    let total_score = run("example.txt");
-    println!("The example score is: {}", total_score);
+    // The example doesn't draw any letters, so its CRT gets printed as is.
+    match run("example.txt") {
+        Ok(machine) => {
+            machine.print_crt();
+            println!("The example score is: {}", machine.signal_strength())
+        }
+        Err(e) => println!("Error: {}", e),
+    }
+
+    match run_both("input.txt") {
+        Ok((total_score, total_score_b)) => {
+            println!("The score is: {} {}", total_score, total_score_b)
+        }
+        Err(e) => println!("Error: {}", e),
+    }
+
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if let Err(e) = run_args(&args) {
+        println!("Error: {}", e);
+    }
+}
 
-    let total_score = run("input.txt");
-    println!("The score is: {}", total_score);
+/// Handles the optional command line arguments, which either run, debug or
+/// disassemble other programs. `--crt` runs a program on a CRT with the
+/// given width, height, sprite width and sample schedule, while `--pbm`
+/// prints the frame a program draws as an image. `--trace csv` or
+/// `--trace json` prints what happened during every cycle. Programs that
+/// don't halt within `MAX_CYCLES` cycles fail instead.
+fn run_args(args: &[String]) -> Result<(), String> {
+    match args
+        .iter()
+        .map(|a| a.as_str())
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        ["--disassemble", path] => print!("{}", asm::disassemble(&read_program(path)?)),
+        ["--trace", format, path] => {
+            let mut machine = Machine::new(read_program(path)?.ops).traced();
+            machine.run()?;
+            let trace = machine.trace().unwrap_or_default();
+            match *format {
+                "csv" => print!("{}", trace::to_csv(trace)),
+                "json" => print!("{}", trace::to_json(trace)),
+                _ => return Err(format!("Unknown trace format: {}", format)),
+            }
+        }
+        ["--pbm", path] => print!("{}", run(path)?.frame.to_pbm(4)),
+        ["--crt", width, height, sprite_width, samples, path] => {
+            let number = |n: &str| n.parse().map_err(|_| format!("Invalid number: {}", n));
+            let config = CrtConfig {
+                width: number(width)?,
+                height: number(height)?,
+                sprite_width: number(sprite_width)?,
+                samples: SampleSchedule::parse(samples)?,
+            };
+            if config.width == 0 || config.height == 0 {
+                return Err("The CRT needs at least one pixel".to_string());
+            }
+            let mut machine = Machine::with_config(read_program(path)?.ops, config);
+            machine.run()?;
+            machine.print_crt();
+            println!("The score of {} is: {}", path, machine.signal_strength());
+        }
+        ["--debug", path] => Debugger::new(read_program(path)?)
+            .repl(io::stdin().lock(), io::stdout())
+            .map_err(|e| e.to_string())?,
+        paths => {
+            for path in paths {
+                let machine = run(path)?;
+                machine.print_crt();
+                println!("The score of {} is: {}", path, machine.signal_strength());
+            }
+        }
+    }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -149,7 +393,327 @@ mod tests {
 
     #[test]
     fn aoc_test() {
-        let total_score = run("input.txt");
+        let (total_score, total_score_b) = run_both("input.txt").unwrap();
         assert_eq!(total_score, 17840);
+        assert_eq!(total_score_b, "EALGULPG");
+    }
+
+    #[test]
+    fn asm_test() {
+        let source = "; counts down\nstart:\n  addx -1 ; one less\n\nloop: noop\nend:";
+        let program = asm::assemble(source).unwrap();
+        assert_eq!(
+            program.ops,
+            [
+                MachineOp::Add(Register::X, Operand::Imm(-1)),
+                MachineOp::NoOp
+            ]
+        );
+        assert_eq!(program.labels["start"], 0);
+        assert_eq!(program.labels["loop"], 1);
+        assert_eq!(program.labels["end"], 2);
+        let listing = asm::disassemble(&program);
+        assert_eq!(listing, "start:\n    addx -1\nloop:\n    noop\nend:\n");
+        assert_eq!(asm::assemble(&listing), Ok(program));
+
+        let input = read_program("input.txt").unwrap();
+        assert_eq!(asm::assemble(&asm::disassemble(&input)), Ok(input));
+
+        assert_eq!(
+            asm::assemble("noop\naddx"),
+            Err("Line 2: Missing addx operand".to_string())
+        );
+        assert_eq!(
+            asm::assemble("noop 1"),
+            Err("Line 1: Unexpected operand: 1".to_string())
+        );
+        assert_eq!(
+            asm::assemble("a: noop\na: noop"),
+            Err("Line 2: Duplicate label: a".to_string())
+        );
+        assert_eq!(
+            asm::assemble("2a: noop"),
+            Err("Line 1: Invalid label: 2a".to_string())
+        );
+        assert_eq!(
+            MachineOp::try_from("jmpx".to_string()),
+            Err("Unknown opcode: jmpx".to_string())
+        );
+    }
+
+    #[test]
+    fn isa_test() {
+        let source = "
+                addy 5
+            loop:
+                addx 2
+                suby 1
+                jnz y loop
+                mulx x
+                jz y skip
+                addx 1000
+            skip:
+                addw x
+                subw -4
+                jmp end
+                halt
+            end:
+                halt
+                addx 1000
+        ";
+        let program = asm::assemble(source).unwrap();
+        assert_eq!(program.ops[3], MachineOp::JumpIfNotZero(Register::Y, -2));
+        assert_eq!(program.ops[9], MachineOp::Jump(2));
+        assert_eq!(
+            asm::assemble(&asm::disassemble(&program)),
+            Ok(program.clone())
+        );
+
+        let mut machine = Machine::new(program.ops);
+        machine.run().unwrap();
+        assert_eq!(machine.x(), 121);
+        assert_eq!(machine.reg(Register::Y), 0);
+        assert_eq!(machine.reg(Register::W), 125);
+        assert_eq!(machine.ip, 11);
+        // 2 for addy, 6 for each of the 5 loops, 4 for mulx, 2 for every
+        // other instruction up to the halt that takes a single cycle.
+        assert_eq!(machine.cycles, 2 + 5 * 6 + 4 + 2 * 4 + 1);
+
+        let mut machine = Machine::new(asm::assemble("addx 1\njmp -5\naddx 1").unwrap().ops);
+        machine.run().unwrap();
+        assert_eq!((machine.x(), machine.cycles), (2, 4));
+
+        let looping = asm::assemble("start: noop\njmp start").unwrap().ops;
+        let mut machine = Machine::new(looping.clone()).traced();
+        assert_eq!(
+            machine.run_for(100),
+            Err("Still running after 100 cycles".to_string())
+        );
+        assert_eq!(machine.trace().map(|t| t.len()), Some(100));
+        assert!(Machine::new(looping).run().is_err());
+
+        assert_eq!(
+            asm::assemble("jz x nowhere"),
+            Err("Line 1: Unknown label: nowhere".to_string())
+        );
+        assert_eq!(
+            asm::assemble("jnz q 1"),
+            Err("Line 1: Unknown register: q".to_string())
+        );
+        assert_eq!(
+            asm::assemble("addq 1"),
+            Err("Line 1: Unknown opcode: addq".to_string())
+        );
+    }
+
+    #[test]
+    fn debugger_test() {
+        let mut debugger = Debugger::new(read_program("example.txt").unwrap());
+        let mut run = |command: &str| debugger.execute(command).unwrap();
+        assert_eq!(
+            run("print"),
+            "cycle 1 ip 0 (addx 15, waited 0) x=1 y=0 z=0 w=0"
+        );
+        assert_eq!(
+            run("step"),
+            "cycle 2 ip 0 (addx 15, waited 1) x=1 y=0 z=0 w=0"
+        );
+        assert_eq!(
+            run("next"),
+            "cycle 3 ip 1 (addx -11, waited 0) x=16 y=0 z=0 w=0"
+        );
+        assert_eq!(
+            run("step 3"),
+            "cycle 6 ip 2 (addx 6, waited 1) x=5 y=0 z=0 w=0"
+        );
+        assert_eq!(run("crt"), format!("##..#{}", "_".repeat(35)));
+
+        run("break cycle 20");
+        assert_eq!(
+            run("continue"),
+            "Breakpoint at cycle 20\ncycle 20 ip 10 (addx -1, waited 0) x=21 y=0 z=0 w=0"
+        );
+        run("break ip 12");
+        run("watch x");
+        assert_eq!(
+            run("next 5"),
+            "Watched x changed from 21 to 20\ncycle 22 ip 11 (addx 5, waited 0) x=20 y=0 z=0 w=0"
+        );
+        run("unwatch x");
+        assert_eq!(
+            run("continue"),
+            "Breakpoint at ip 12\ncycle 24 ip 12 (addx -1, waited 0) x=25 y=0 z=0 w=0"
+        );
+        run("delete ip 12");
+        assert!(run("continue").starts_with("Halted\ncycle 241 ip 146 (halted"));
+        assert_eq!(run("crt"), "#######.......#######.......#######.....");
+        assert_eq!(
+            run("step"),
+            "Halted\ncycle 241 ip 146 (halted, waited 0) x=17 y=0 z=0 w=0"
+        );
+
+        let mut debugger = Debugger::new(asm::assemble("start: noop\njmp start").unwrap());
+        assert_eq!(
+            debugger.execute("break ip start"),
+            Ok("Breakpoint at ip 0".to_string())
+        );
+        assert_eq!(
+            debugger.execute("next 2"),
+            Ok("Breakpoint at ip 0\ncycle 4 ip 0 (noop, waited 0) x=1 y=0 z=0 w=0".to_string())
+        );
+        assert_eq!(
+            debugger.execute("delete cycle 3"),
+            Err("No breakpoint at cycle 3".to_string())
+        );
+        assert_eq!(
+            debugger.execute("step x"),
+            Err("Invalid count: x".to_string())
+        );
+        assert_eq!(
+            debugger.execute("watch v"),
+            Err("Unknown register: v".to_string())
+        );
+        assert!(debugger.execute("jump").is_err());
+        debugger.execute("delete ip start").unwrap();
+        assert!(debugger
+            .execute("continue")
+            .unwrap()
+            .starts_with("Still running after 1048576 cycles\ncycle 1048580 ip 1"));
+        assert!(debugger
+            .execute("next 10000000")
+            .unwrap()
+            .starts_with("Still running after 1048576 cycles\n"));
+
+        let mut output = vec![];
+        let mut debugger = Debugger::new(asm::assemble("addx 2").unwrap());
+        debugger
+            .repl("watch x\ncontinue\nquit\nprint".as_bytes(), &mut output)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "cycle 1 ip 0 (addx 2, waited 0) x=1 y=0 z=0 w=0\n> Watching x\n> Watched x changed from 1 to 3\ncycle 3 ip 1 (halted, waited 0) x=3 y=0 z=0 w=0\n> "
+        );
+    }
+
+    #[test]
+    fn ocr_test() {
+        let rows = [
+            "#..#..###.....",
+            "#..#...#......",
+            "####...#......",
+            "#..#...#......",
+            "#..#...#......",
+            "#..#..###.....",
+        ];
+        let crt = |width: usize, height: usize| {
+            let mut frame = Framebuffer::new(width, height);
+            for (y, row) in rows.iter().take(height).enumerate() {
+                for (x, c) in row.chars().take(width).enumerate() {
+                    frame.set(x, y, c == '#');
+                }
+            }
+            frame
+        };
+        let mut hi = crt(14, 6);
+        assert_eq!(ocr::recognize(&hi), Ok("HI ".to_string()));
+        assert_eq!(
+            ocr::recognize(&crt(12, 6)),
+            Err("Incomplete glyph at column 10".to_string())
+        );
+        assert!(ocr::recognize(&crt(14, 5)).is_err());
+
+        hi.set(7, 5, false);
+        assert_eq!(
+            ocr::recognize(&hi),
+            Err("Unrecognised glyph at column 5:\n.###\n..#.\n..#.\n..#.\n..#.\n.#.#".to_string())
+        );
+
+        let example = run("example.txt").unwrap();
+        assert!(example.read_crt().is_err());
+    }
+
+    #[test]
+    fn crt_test() {
+        let program = asm::assemble("addx 2\nnoop\naddx -3\nnoop\nnoop").unwrap();
+        let config = CrtConfig {
+            width: 3,
+            height: 2,
+            sprite_width: 2,
+            samples: SampleSchedule::Cycles(vec![2, 5]),
+        };
+        let mut machine = Machine::with_config(program.ops.clone(), config.clone());
+        machine.run().unwrap();
+        // x is 1, 1, 3, 3, 3, 0 and 0 during the seven cycles, the last one
+        // of which starts over with a blank frame.
+        assert_eq!(machine.signal_strength(), 2 + 5 * 3);
+        assert_eq!(machine.frame.render('#', '.'), "#..\n...");
+        assert_eq!(machine.beam, 1);
+
+        let mut machine = Machine::with_config(
+            program.ops,
+            CrtConfig {
+                height: 3,
+                sprite_width: 1,
+                ..config
+            },
+        );
+        machine.run().unwrap();
+        assert_eq!(machine.frame.to_string(), ".#.\n...\n#..");
+        assert_eq!(
+            machine.frame.to_pbm(2),
+            "P1\n6 6\n0 0 1 1 0 0\n0 0 1 1 0 0\n0 0 0 0 0 0\n0 0 0 0 0 0\n1 1 0 0 0 0\n1 1 0 0 0 0\n"
+        );
+        machine.frame.clear();
+        assert_eq!(machine.frame.render('#', ' '), "   \n   \n   ");
+
+        assert_eq!(
+            SampleSchedule::parse("2, 6"),
+            Ok(SampleSchedule::Cycles(vec![2, 6]))
+        );
+        assert!(SampleSchedule::parse("20+0").is_err());
+        let every = SampleSchedule::parse("20+40").unwrap();
+        assert_eq!(every, CrtConfig::default().samples);
+        assert!(every.contains(20) && every.contains(220));
+        assert!(!every.contains(0) && !every.contains(40));
+        assert_eq!(run("input.txt").unwrap().frame.height(), 6);
+    }
+
+    #[test]
+    fn trace_test() {
+        let program = asm::assemble("noop\naddx 3\naddx -5").unwrap();
+        let mut machine = Machine::new(program.ops).traced();
+        machine.run().unwrap();
+        let trace = machine.trace().unwrap();
+        assert_eq!(trace.len(), machine.cycles);
+        assert_eq!(
+            trace[2],
+            TraceEntry {
+                cycle: 3,
+                ip: 1,
+                op: MachineOp::Add(Register::X, Operand::Imm(3)),
+                waited: 1,
+                x_before: 1,
+                x_after: 4,
+                pixel: true,
+            }
+        );
+        assert_eq!(
+            trace::to_csv(&trace[..2]),
+            "cycle,ip,opcode,waited,x_before,x_after,pixel\n1,0,noop,0,1,1,#\n2,1,addx 3,0,1,1,#\n"
+        );
+        assert_eq!(
+            trace::to_json(&trace[3..]),
+            "[\n  {\"cycle\":4,\"ip\":2,\"opcode\":\"addx -5\",\"waited\":0,\"x_before\":4,\"x_after\":4,\"pixel\":\"#\"},\n  {\"cycle\":5,\"ip\":2,\"opcode\":\"addx -5\",\"waited\":1,\"x_before\":4,\"x_after\":-1,\"pixel\":\"#\"}\n]\n"
+        );
+        assert_eq!(trace::to_json(&[]), "[]\n");
+
+        let untraced = run("input.txt").unwrap();
+        assert!(untraced.trace().is_none());
+        let mut machine = Machine::new(read_program("input.txt").unwrap().ops).traced();
+        machine.run().unwrap();
+        assert_eq!(untraced.frame, machine.frame);
+        let trace = machine.trace().unwrap();
+        assert_eq!(trace.len(), 240);
+        assert!(trace.windows(2).all(|w| w[0].x_after == w[1].x_before));
     }
 }
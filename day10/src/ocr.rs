@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+
+use crate::crt::Framebuffer;
+
+const GLYPH_WIDTH: usize = 4;
+const GLYPH_HEIGHT: usize = 6;
+/// Glyphs are separated by a single blank column.
+const GLYPH_STRIDE: usize = GLYPH_WIDTH + 1;
+
+const FONT_LETTERS: &str = "ABCEFGHIJKLOPRSUYZ";
+const FONT: [&str; GLYPH_HEIGHT] = [
+    ".##..###...##..####.####..##..#..#..###...##.#..#.#.....##..###..###...###.#..#.#....####",
+    "#..#.#..#.#..#.#....#....#..#.#..#...#.....#.#.#..#....#..#.#..#.#..#.#....#..#.#.......#",
+    "#..#.###..#....###..###..#....####...#.....#.##...#....#..#.#..#.#..#.#....#..#..#.#...#.",
+    "####.#..#.#....#....#....#.##.#..#...#.....#.#.#..#....#..#.###..###...##..#..#...#...#..",
+    "#..#.#..#.#..#.#....#....#..#.#..#...#..#..#.#.#..#....#..#.#....#.#.....#.#..#...#..#...",
+    "#..#.###...##..####.#.....###.#..#..###..##..#..#.####..##..#....#..#.###...##....#..####",
+];
+
+/// Packs the glyph whose top left corner is in column `x` into a bitmask,
+/// where `pixel(x, y)` tells whether a pixel is lit.
+fn glyph(pixel: impl Fn(usize, usize) -> bool, x: usize) -> u32 {
+    let mut ret = 0;
+    for y in 0..GLYPH_HEIGHT {
+        for x in x..x + GLYPH_WIDTH {
+            ret = ret << 1 | pixel(x, y) as u32;
+        }
+    }
+    ret
+}
+
+fn font() -> HashMap<u32, char> {
+    let rows = FONT.map(|row| row.as_bytes());
+    FONT_LETTERS
+        .chars()
+        .enumerate()
+        .map(|(i, letter)| (glyph(|x, y| rows[y][x] == b'#', i * GLYPH_STRIDE), letter))
+        .collect()
+}
+
+fn draw(glyph: u32) -> String {
+    (0..GLYPH_HEIGHT)
+        .map(|y| {
+            (0..GLYPH_WIDTH)
+                .map(|x| {
+                    let bit = (GLYPH_HEIGHT - y) * GLYPH_WIDTH - x - 1;
+                    if glyph >> bit & 1 == 1 {
+                        '#'
+                    } else {
+                        '.'
+                    }
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Reads the capital letters drawn on a CRT in the usual 4x6 font, where
+/// blank glyphs are read as spaces.
+pub fn recognize(frame: &Framebuffer) -> Result<String, String> {
+    if frame.height() != GLYPH_HEIGHT {
+        return Err(format!(
+            "Expected a CRT that's {} pixels high but it's {}",
+            GLYPH_HEIGHT,
+            frame.height()
+        ));
+    }
+    let font = font();
+    let width = frame.width();
+    (0..width)
+        .step_by(GLYPH_STRIDE)
+        .map(|x| {
+            if x + GLYPH_WIDTH > width {
+                return Err(format!("Incomplete glyph at column {}", x));
+            }
+            match glyph(|x, y| frame.get(x, y), x) {
+                0 => Ok(' '),
+                g => font.get(&g).copied().ok_or(format!(
+                    "Unrecognised glyph at column {}:\n{}",
+                    x,
+                    draw(g)
+                )),
+            }
+        })
+        .collect()
+}
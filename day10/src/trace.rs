@@ -0,0 +1,68 @@
+use std::fmt::Write;
+
+use crate::MachineOp;
+
+/// Everything that happened during a single cycle.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub cycle: usize,
+    pub ip: usize,
+    pub op: MachineOp,
+    /// Number of cycles the instruction had already waited for.
+    pub waited: usize,
+    pub x_before: i32,
+    pub x_after: i32,
+    pub pixel: bool,
+}
+
+const COLUMNS: [&str; 7] = [
+    "cycle", "ip", "opcode", "waited", "x_before", "x_after", "pixel",
+];
+
+impl TraceEntry {
+    /// The values of every column, with the pixel drawn as `#` or `.`.
+    fn fields(&self) -> [String; 7] {
+        [
+            self.cycle.to_string(),
+            self.ip.to_string(),
+            self.op.to_string(),
+            self.waited.to_string(),
+            self.x_before.to_string(),
+            self.x_after.to_string(),
+            if self.pixel { "#" } else { "." }.to_string(),
+        ]
+    }
+}
+
+/// Writes the trace as CSV with a header row. None of the fields can
+/// contain commas or quotes, so nothing needs to be escaped.
+pub fn to_csv(trace: &[TraceEntry]) -> String {
+    let mut ret = COLUMNS.join(",") + "\n";
+    for entry in trace {
+        writeln!(ret, "{}", entry.fields().join(",")).unwrap();
+    }
+    ret
+}
+
+/// Writes the trace as a JSON array with one object per line.
+pub fn to_json(trace: &[TraceEntry]) -> String {
+    let lines = trace
+        .iter()
+        .map(|entry| {
+            let fields = COLUMNS
+                .iter()
+                .zip(entry.fields())
+                .map(|(column, value)| match *column {
+                    "opcode" | "pixel" => format!("\"{}\":\"{}\"", column, value),
+                    _ => format!("\"{}\":{}", column, value),
+                })
+                .collect::<Vec<_>>();
+            format!("  {{{}}}", fields.join(","))
+        })
+        .collect::<Vec<_>>();
+    if lines.is_empty() {
+        "[]\n".to_string()
+    } else {
+        format!("[\n{}\n]\n", lines.join(",\n"))
+    }
+}
@@ -0,0 +1,211 @@
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+impl BinOp {
+    fn symbol(&self) -> char {
+        match self {
+            Self::Add => '+',
+            Self::Sub => '-',
+            Self::Mul => '*',
+            Self::Div => '/',
+        }
+    }
+}
+
+/// The right hand side of a monkey's `new = ...` operation.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum Expr {
+    #[default]
+    Old,
+    Const(i64),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Old,
+    Number(i64),
+    Op(BinOp),
+    Open,
+    Close,
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let mut iter = s.chars().peekable();
+    let mut ret = vec![];
+    while let Some(c) = iter.next() {
+        ret.push(match c {
+            c if c.is_whitespace() => continue,
+            '+' => Token::Op(BinOp::Add),
+            '-' => Token::Op(BinOp::Sub),
+            '*' => Token::Op(BinOp::Mul),
+            '/' => Token::Op(BinOp::Div),
+            '(' => Token::Open,
+            ')' => Token::Close,
+            c if c.is_ascii_digit() => {
+                let mut digits = c.to_string();
+                while let Some(c) = iter.next_if(|c| c.is_ascii_digit()) {
+                    digits.push(c);
+                }
+                Token::Number(
+                    digits
+                        .parse()
+                        .map_err(|_| format!("Number out of range: {}", digits))?,
+                )
+            }
+            c if c.is_ascii_alphabetic() => match read_word(c, &mut iter).as_str() {
+                "old" => Token::Old,
+                word => return Err(format!("Unknown identifier: {}", word)),
+            },
+            c => return Err(format!("Unexpected character: '{}'", c)),
+        });
+    }
+    Ok(ret)
+}
+
+fn read_word(first: char, iter: &mut Peekable<Chars>) -> String {
+    let mut ret = first.to_string();
+    while let Some(c) = iter.next_if(|c| c.is_ascii_alphanumeric()) {
+        ret.push(c);
+    }
+    ret
+}
+
+/// Recursive descent parser where `*` and `/` bind tighter than `+` and
+/// `-`, and operators of the same precedence associate to the left.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let ret = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        ret
+    }
+
+    fn binary(
+        &mut self,
+        ops: [BinOp; 2],
+        operand: fn(&mut Self) -> Result<Expr, String>,
+    ) -> Result<Expr, String> {
+        let mut ret = operand(self)?;
+        while let Some(Token::Op(op)) = self.peek() {
+            let op = *op;
+            if !ops.contains(&op) {
+                break;
+            }
+            self.pos += 1;
+            ret = Expr::Binary(op, Box::new(ret), Box::new(operand(self)?));
+        }
+        Ok(ret)
+    }
+
+    fn sum(&mut self) -> Result<Expr, String> {
+        self.binary([BinOp::Add, BinOp::Sub], Self::product)
+    }
+
+    fn product(&mut self) -> Result<Expr, String> {
+        self.binary([BinOp::Mul, BinOp::Div], Self::factor)
+    }
+
+    fn factor(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Old) => Ok(Expr::Old),
+            Some(Token::Number(n)) => Ok(Expr::Const(n)),
+            Some(Token::Op(BinOp::Sub)) => Ok(Expr::Binary(
+                BinOp::Sub,
+                Box::new(Expr::Const(0)),
+                Box::new(self.factor()?),
+            )),
+            Some(Token::Open) => {
+                let ret = self.sum()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(ret),
+                    _ => Err("Missing closing parenthesis".to_string()),
+                }
+            }
+            Some(token) => Err(format!("Unexpected token: {:?}", token)),
+            None => Err("Unexpected end of expression".to_string()),
+        }
+    }
+}
+
+impl Expr {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            pos: 0,
+        };
+        let ret = parser.sum()?;
+        match parser.peek() {
+            None => Ok(ret),
+            Some(token) => Err(format!("Unexpected token: {:?}", token)),
+        }
+    }
+
+    /// Whether the expression can be evaluated on worry levels that are only
+    /// known modulo some number, which holds for everything but division.
+    pub fn is_modular(&self) -> bool {
+        match self {
+            Self::Old | Self::Const(_) => true,
+            Self::Binary(op, a, b) => *op != BinOp::Div && a.is_modular() && b.is_modular(),
+        }
+    }
+
+    /// Evaluates the expression, reducing every intermediate result modulo
+    /// `modulus` if there is one. Division rounds towards negative infinity.
+    pub fn eval(&self, old: i64, modulus: Option<i64>) -> Result<i64, String> {
+        let reduce = |v: i64| match modulus {
+            Some(m) => v.rem_euclid(m),
+            None => v,
+        };
+        match self {
+            Self::Old => Ok(reduce(old)),
+            Self::Const(c) => Ok(reduce(*c)),
+            Self::Binary(op, a, b) => {
+                let (a, b) = (a.eval(old, modulus)?, b.eval(old, modulus)?);
+                let ret = match op {
+                    BinOp::Add => a.checked_add(b),
+                    BinOp::Sub => a.checked_sub(b),
+                    BinOp::Mul => a.checked_mul(b),
+                    BinOp::Div => match modulus {
+                        Some(m) => return Err(format!("Can't divide worry levels modulo {}", m)),
+                        None if b == 0 => return Err("Division by zero".to_string()),
+                        None => a.checked_div_euclid(b),
+                    },
+                };
+                ret.map(reduce).ok_or(format!(
+                    "Worry level overflowed: {} {} {}",
+                    a,
+                    op.symbol(),
+                    b
+                ))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Old => write!(f, "old"),
+            Self::Const(c) => write!(f, "{}", c),
+            Self::Binary(op, a, b) => write!(f, "({} {} {})", a, op.symbol(), b),
+        }
+    }
+}
@@ -1,29 +1,22 @@
+mod expr;
+
 use std::fs::File;
 use std::io::{BufRead, BufReader};
+use std::str::FromStr;
 
-#[derive(Clone, Debug, Default)]
-enum MonkeyOp {
-    #[default]
-    MultiplyOld,
-    MultiplyImm(i64),
-    AddImm(i64),
-}
+use expr::Expr;
 
-impl MonkeyOp {
-    pub fn apply(&self, old: i64) -> i64 {
-        match self {
-            Self::MultiplyOld => old * old,
-            Self::MultiplyImm(imm) => old * imm,
-            Self::AddImm(imm) => old + imm,
-        }
-    }
+/// Parses the number a line of a monkey's description ends with.
+fn last_number<T: FromStr>(s: &str) -> Result<T, String> {
+    let n = s.split(' ').next_back().unwrap_or("");
+    n.parse().map_err(|_| format!("Invalid number: {}", n))
 }
 
 #[derive(Clone, Debug, Default)]
 struct Monkey {
     id: usize,
     items: Vec<i64>,
-    op: MonkeyOp,
+    op: Expr,
     test_divisor: i64,
     true_monkey_id: usize,
     false_monkey_id: usize,
@@ -32,77 +25,69 @@ struct Monkey {
 }
 
 impl Monkey {
-    pub fn new(init: &Vec<String>) -> Self {
+    pub fn new(init: &[String]) -> Result<Self, String> {
         let mut ret = Self {
             ..Default::default()
         };
-        init.iter().for_each(|line| {
-            let p = line.split(":").collect::<Vec<_>>();
-            if p[0].starts_with("Monkey") {
-                ret.id = p[0]
-                    .split(" ")
-                    .skip(1)
-                    .next()
-                    .unwrap()
-                    .parse::<usize>()
-                    .unwrap();
-            } else {
-                match p[0] {
-                    "Starting items" => {
-                        ret.items = p[1]
-                            .split(",")
-                            .map(|p| p.trim().parse().unwrap())
-                            .collect::<Vec<_>>()
-                    }
-                    "Test" => ret.test_divisor = p[1].split(" ").last().unwrap().parse().unwrap(),
-                    "If true" => {
-                        ret.true_monkey_id = p[1].split(" ").last().unwrap().parse().unwrap()
-                    }
-                    "If false" => {
-                        ret.false_monkey_id = p[1].split(" ").last().unwrap().parse().unwrap()
-                    }
-                    "Operation" => {
-                        let symbols: [&str; 3] = p[1]
-                            .split(" ")
-                            .skip(3)
-                            .collect::<Vec<_>>()
-                            .try_into()
-                            .unwrap();
-                        ret.op = match symbols {
-                            ["old", "*", "old"] => MonkeyOp::MultiplyOld,
-                            ["old", "*", imm] => MonkeyOp::MultiplyImm(imm.parse().unwrap()),
-                            ["old", "+", imm] => MonkeyOp::AddImm(imm.parse().unwrap()),
-                            _ => panic!("Unsupported operations: {}", p[1]),
-                        }
-                    }
-                    _ => panic!("Unknown key: {}", p[0]),
+        for line in init {
+            let (key, value) = line.split_once(':').unwrap_or((line, ""));
+            if key.starts_with("Monkey") {
+                ret.id = last_number(key)?;
+                continue;
+            }
+            match key {
+                "Starting items" => {
+                    ret.items = value
+                        .split(',')
+                        .map(|p| p.trim().parse().map_err(|_| format!("Invalid item: {}", p)))
+                        .collect::<Result<Vec<_>, _>>()?
                 }
+                "Test" => ret.test_divisor = last_number(value)?,
+                "If true" => ret.true_monkey_id = last_number(value)?,
+                "If false" => ret.false_monkey_id = last_number(value)?,
+                "Operation" => {
+                    let expr = value
+                        .trim()
+                        .strip_prefix("new =")
+                        .ok_or(format!("Expected 'new = ...': {}", value))?;
+                    ret.op = Expr::parse(expr)
+                        .map_err(|e| format!("Monkey {}: {}: {}", ret.id, e, expr.trim()))?;
+                }
+                _ => return Err(format!("Unknown key: {}", key)),
             }
-        });
-        println!("{:?}", ret);
-        ret
+        }
+        if ret.test_divisor == 0 {
+            return Err(format!(
+                "Monkey {}: Can't test for divisibility by 0",
+                ret.id
+            ));
+        }
+        Ok(ret)
     }
 
-    pub fn turn(&mut self, monkey_modulo: Option<i64>) -> Vec<(usize, i64)> {
+    /// Inspects and throws every item. Worry levels are divided by three
+    /// after the inspection if `relief` is set, and kept modulo `modulus`
+    /// if there is one.
+    pub fn turn(
+        &mut self,
+        relief: bool,
+        modulus: Option<i64>,
+    ) -> Result<Vec<(usize, i64)>, String> {
+        self.inspection_counter += self.items.len();
         self.items
             .drain(..)
             .map(|item| {
-                self.inspection_counter += 1;
-                let worry = if let Some(_) = monkey_modulo {
-                    self.op.apply(item)
-                } else {
-                    self.op.apply(item) / 3
-                };
-                let dest = if worry % self.test_divisor == 0 {
+                let worry = self
+                    .op
+                    .eval(item, modulus)
+                    .map_err(|e| format!("Monkey {}: new = {}: {}", self.id, self.op, e))?;
+                let worry = if relief { worry / 3 } else { worry };
+                let dest = if worry.rem_euclid(self.test_divisor) == 0 {
                     self.true_monkey_id
                 } else {
                     self.false_monkey_id
                 };
-                if let Some(m) = monkey_modulo {
-                    (dest, worry % m)
-                } else {
-                    (dest, worry)
-                }
+                Ok((dest, worry))
             })
             .collect()
     }
@@ -115,20 +100,27 @@ impl Monkey {
         self.items.push(item);
     }
 
-    pub fn round(monkeys: &mut Vec<Monkey>, no_worries: bool) {
-        let monkey_modulo: Option<i64> = if no_worries {
+    /// Lets every monkey take a turn. Without relief the worry levels only
+    /// matter modulo the product of all divisors, as long as no monkey
+    /// divides them. Otherwise they're kept exactly and may overflow.
+    pub fn round(monkeys: &mut [Monkey], no_worries: bool) -> Result<(), String> {
+        let modulus = if no_worries || !monkeys.iter().all(|m| m.op.is_modular()) {
             None
         } else {
             Some(monkeys.iter().map(|m| m.test_divisor).product())
         };
         for i in 0..monkeys.len() {
-            for (monkey, item) in monkeys[i].turn(monkey_modulo) {
-                monkeys[monkey].catch(item);
+            for (monkey, item) in monkeys[i].turn(no_worries, modulus)? {
+                monkeys
+                    .get_mut(monkey)
+                    .ok_or(format!("Monkey {} doesn't exist", monkey))?
+                    .catch(item);
             }
         }
+        Ok(())
     }
 
-    pub fn business(monkeys: &Vec<Monkey>) -> usize {
+    pub fn business(monkeys: &[Monkey]) -> usize {
         let mut arr = monkeys
             .iter()
             .map(|m| m.inspection_counter())
@@ -139,14 +131,14 @@ impl Monkey {
     }
 }
 
-fn run_both(path: &str) -> (usize, usize) {
-    let file = File::open(path).unwrap();
+fn read_monkeys(path: &str) -> Result<Vec<Monkey>, String> {
+    let file = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
     let reader = BufReader::new(file).lines();
-    let mut monkeys = reader
+    reader
         .fold(vec![vec![]], |mut a, line| {
             let line = line.unwrap();
             let trimmed_line = line.trim();
-            if trimmed_line.len() == 0 {
+            if trimmed_line.is_empty() {
                 a.push(vec![]);
             } else {
                 a.last_mut().unwrap().push(trimmed_line.to_string());
@@ -154,30 +146,43 @@ fn run_both(path: &str) -> (usize, usize) {
             a
         })
         .iter()
+        .filter(|m| !m.is_empty())
         .map(|m| Monkey::new(m))
-        .collect::<Vec<_>>();
+        .collect()
+}
+
+fn run_both(path: &str) -> Result<(usize, usize), String> {
+    let mut monkeys = read_monkeys(path)?;
     let a = {
         let mut a = monkeys.clone();
-        for r in 0..20 {
-            Monkey::round(&mut a, true);
+        for _ in 0..20 {
+            Monkey::round(&mut a, true)?;
         }
         Monkey::business(&a)
     };
     let b = {
-        for r in 0..10000 {
-            Monkey::round(&mut monkeys, false);
+        for _ in 0..10000 {
+            Monkey::round(&mut monkeys, false)?;
         }
         Monkey::business(&monkeys)
     };
-    (a, b)
+    Ok((a, b))
 }
 
 fn main() {
-    let (total_score, total_score_b) = run_both("example.txt");
-    println!("The example score is: {} {}", total_score, total_score_b);
+    match run_both("example.txt") {
+        Ok((total_score, total_score_b)) => {
+            println!("The example score is: {} {}", total_score, total_score_b)
+        }
+        Err(e) => println!("Error: {}", e),
+    }
 
-    let (total_score, total_score_b) = run_both("input.txt");
-    println!("The score is: {} {}", total_score, total_score_b);
+    match run_both("input.txt") {
+        Ok((total_score, total_score_b)) => {
+            println!("The score is: {} {}", total_score, total_score_b)
+        }
+        Err(e) => println!("Error: {}", e),
+    }
 }
 
 #[cfg(test)]
@@ -186,8 +191,68 @@ mod tests {
 
     #[test]
     fn aoc_test() {
-        let (total_score, total_score_b) = run_both("input.txt");
+        let (total_score, total_score_b) = run_both("input.txt").unwrap();
         assert_eq!(total_score, 55930);
         assert_eq!(total_score_b, 14636993466);
     }
+
+    #[test]
+    fn expr_test() {
+        let expr = Expr::parse("(old - 1) * (2 + old) + 3 * old / 2").unwrap();
+        assert_eq!(
+            expr.to_string(),
+            "(((old - 1) * (2 + old)) + ((3 * old) / 2))"
+        );
+        assert_eq!(expr.eval(5, None), Ok(4 * 7 + 7));
+        assert_eq!(Expr::parse("7 - old - -2").unwrap().eval(3, None), Ok(6));
+        assert_eq!(Expr::parse("old * old").unwrap().eval(9, Some(10)), Ok(1));
+        assert_eq!(Expr::parse("-7 / 2").unwrap().eval(0, None), Ok(-4));
+        assert!(!expr.is_modular());
+        assert!(expr.eval(5, Some(10)).is_err());
+        assert_eq!(
+            Expr::parse("old / (old - old)").unwrap().eval(1, None),
+            Err("Division by zero".to_string())
+        );
+        assert!(Expr::parse("old * old")
+            .unwrap()
+            .eval(i64::MAX, None)
+            .is_err());
+        assert_eq!(
+            Expr::parse("old * new"),
+            Err("Unknown identifier: new".to_string())
+        );
+        assert!(Expr::parse("(old + 1").is_err());
+        assert!(Expr::parse("old old").is_err());
+        assert!(Expr::parse("").is_err());
+
+        // Reducing modulo the product of all divisors doesn't change where
+        // the items get thrown to.
+        let description = |op: &str| {
+            [
+                "Monkey 0:",
+                "Starting items: 79, 98, 1003, 5",
+                &format!("Operation: new = {}", op),
+                "Test: divisible by 13",
+                "If true: throw to monkey 1",
+                "If false: throw to monkey 2",
+            ]
+            .map(|l| l.to_string())
+        };
+        let mut monkey = Monkey::new(&description("(old - 7) * (old + 5) - 3 * old")).unwrap();
+        let exact = monkey.clone().turn(false, None).unwrap();
+        let reduced = monkey.turn(false, Some(13 * 17)).unwrap();
+        assert_eq!(reduced.len(), 4);
+        for ((a, exact), (b, reduced)) in exact.iter().zip(reduced.iter()) {
+            assert_eq!(a, b);
+            assert_eq!(exact.rem_euclid(13 * 17), *reduced);
+        }
+
+        // Monkeys that divide can't be reduced, so the worry levels grow.
+        let mut monkeys = read_monkeys("example.txt").unwrap();
+        monkeys[0] = Monkey::new(&description("old * old / 2")).unwrap();
+        monkeys[0].id = 0;
+        assert_eq!(Monkey::round(&mut monkeys, false), Ok(()));
+        assert!((0..20).any(|_| Monkey::round(&mut monkeys, false).is_err()));
+        assert!(Monkey::new(&description("old ^ 2")).is_err());
+    }
 }
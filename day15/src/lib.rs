@@ -1,3 +1,4 @@
+use interval::{Interval, IntervalSet};
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 
@@ -8,15 +9,15 @@ pub struct Sensor {
 }
 
 pub enum IntervalCollation {
-    Single([i64; 2]),
-    Double([i64; 2], [i64; 2]),
+    Single(Interval<i64>),
+    Double(Interval<i64>, Interval<i64>),
 }
 
 #[inline]
-fn sensor_to_interval(s: &Sensor, y: i64) -> Option<[i64; 2]> {
-    let r = (s.radius - (s.pos[1] - y).abs()).max(-1);
+fn sensor_to_interval(s: &Sensor, y: i64) -> Option<Interval<i64>> {
+    let r = s.radius - (s.pos[1] - y).abs();
     if r >= 0 {
-        Some([s.pos[0] - r, s.pos[0] + r])
+        Some(Interval::new(s.pos[0] - r, s.pos[0] + r))
     } else {
         None
     }
@@ -24,54 +25,32 @@ fn sensor_to_interval(s: &Sensor, y: i64) -> Option<[i64; 2]> {
 
 #[inline]
 fn collated_intervals(
-    buf: &mut Vec<[i64; 2]>,
-    collated: &mut Vec<[i64; 2]>,
-    sensors: &Vec<Sensor>,
+    collated: &mut IntervalSet<i64>,
+    sensors: &[Sensor],
     y: i64,
 ) -> IntervalCollation {
-    buf.clear();
     collated.clear();
-
-    sensors
-        .iter()
-        .filter_map(|s| sensor_to_interval(s, y))
-        .for_each(|e| buf.push(e));
-    buf.sort_by(|a, b| a[0].cmp(&b[0]));
-    let col = buf.iter().copied().reduce(|a, b| {
-        if a[1] < b[0] {
-            // No overlap
-            collated.push(a);
-            b
-        } else {
-            // Overlap
-            [a[0].min(b[0]), a[1].max(b[1])]
-        }
-    });
-    collated.push(col.unwrap_or([0, 0]));
-    if collated.len() == 1 {
-        IntervalCollation::Single(collated[0])
-    } else {
-        IntervalCollation::Double(collated[0], collated[1])
+    collated.extend(sensors.iter().filter_map(|s| sensor_to_interval(s, y)));
+    match collated.intervals() {
+        [] => IntervalCollation::Single(Interval::new(0, 0)),
+        [a] => IntervalCollation::Single(*a),
+        [a, b, ..] => IntervalCollation::Double(*a, *b),
     }
 }
 
-fn no_beacon_count(sensors: &Vec<Sensor>, y: i64) -> i64 {
-    let mut buf = Vec::with_capacity(64);
-    let mut collated = Vec::with_capacity(4);
-    match collated_intervals(&mut buf, &mut collated, sensors, y) {
-        IntervalCollation::Single(a) => a[1] - a[0],
-        IntervalCollation::Double(a, b) => (a[1] - a[0]) + (b[1] - b[0]),
+fn no_beacon_count(sensors: &[Sensor], y: i64) -> i64 {
+    let mut collated = IntervalSet::with_capacity(64);
+    match collated_intervals(&mut collated, sensors, y) {
+        IntervalCollation::Single(a) => a.size() - 1,
+        IntervalCollation::Double(a, b) => (a.size() - 1) + (b.size() - 1),
     }
 }
 
-fn search_for_beacon(sensors: &Vec<Sensor>) -> i64 {
-    let mut buf = Vec::with_capacity(64);
-    let mut collated = Vec::with_capacity(4);
+fn search_for_beacon(sensors: &[Sensor]) -> i64 {
+    let mut collated = IntervalSet::with_capacity(64);
     for y in 0..4000000 {
-        if let IntervalCollation::Double(a, _) =
-            collated_intervals(&mut buf, &mut collated, sensors, y)
-        {
-            return (a[1] + 1) * 4000000 + y;
+        if let IntervalCollation::Double(a, _) = collated_intervals(&mut collated, sensors, y) {
+            return (a.end() + 1) * 4000000 + y;
         }
     }
     panic!("Couldn't determine beacon position");
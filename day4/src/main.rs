@@ -1,34 +1,109 @@
+use interval::{Interval, IntervalSet};
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 
-fn run(path: &str) -> i32 {
+fn parse_interval(s: &str) -> Interval<i32> {
+    let (start, end) = s.split_once('-').unwrap();
+    Interval::new(start.parse().unwrap(), end.parse().unwrap())
+}
+
+fn read_pairs(path: &str) -> Vec<(Interval<i32>, Interval<i32>)> {
     let file = File::open(path).unwrap();
     BufReader::new(file)
         .lines()
         .map(|line| {
             let line = line.unwrap();
-            let parts = line.split(",").map(|s| {
-                s.split("-").map(|v| v.parse::<i32>().unwrap()).collect::<Vec<_>>()
-            }).collect::<Vec<_>>();
-            ((parts[0][0] >= parts[1][0] && parts[0][1] <= parts[1][1])
-            || (parts[0][0] <= parts[1][0] && parts[0][1] >= parts[1][1])) as i32
-        }).sum()
+            let (a, b) = line.split_once(',').unwrap();
+            (parse_interval(a), parse_interval(b))
+        })
+        .collect()
+}
+
+fn run(path: &str) -> i32 {
+    read_pairs(path)
+        .iter()
+        .filter(|(a, b)| a.contains(b) || b.contains(a))
+        .count() as i32
 }
 
 fn run_two(path: &str) -> i32 {
-    let file = File::open(path).unwrap();
-    BufReader::new(file)
-        .lines()
-        .map(|line| {
-            let line = line.unwrap();
-            let parts = line.split(",").map(|s| {
-                s.split("-").map(|v| v.parse::<i32>().unwrap()).collect::<Vec<_>>()
-            }).collect::<Vec<_>>();
-            ((parts[0][0] >= parts[1][0] && parts[0][0] <= parts[1][1]) ||
-            (parts[0][1] >= parts[1][0] && parts[0][1] <= parts[1][1]) ||
-            (parts[1][0] >= parts[0][0] && parts[1][0] <= parts[0][1]) ||
-            (parts[1][1] >= parts[0][0] && parts[1][1] <= parts[0][1])) as i32
-        }).sum()
+    read_pairs(path)
+        .iter()
+        .filter(|(a, b)| a.overlaps(b))
+        .count() as i32
+}
+
+#[derive(Clone, Debug)]
+struct Coverage {
+    assigned: IntervalSet<i32>,
+    uncovered: Vec<Interval<i32>>,
+    shared: IntervalSet<i32>,
+    redundant: Vec<Option<Interval<i32>>>,
+}
+
+impl Coverage {
+    pub fn new(pairs: &[(Interval<i32>, Interval<i32>)]) -> Self {
+        let assigned = pairs
+            .iter()
+            .flat_map(|(a, b)| [*a, *b])
+            .collect::<IntervalSet<_>>();
+        let uncovered = assigned.gaps().collect();
+        let redundant = pairs.iter().map(|(a, b)| a.intersection(b)).collect();
+        Self {
+            assigned,
+            uncovered,
+            shared: Self::shared_sections(pairs),
+            redundant,
+        }
+    }
+
+    /// Sweeps over all assignment boundaries and collects every section
+    /// that is covered by at least two elves.
+    fn shared_sections(pairs: &[(Interval<i32>, Interval<i32>)]) -> IntervalSet<i32> {
+        let mut events = pairs
+            .iter()
+            .flat_map(|(a, b)| [*a, *b])
+            .flat_map(|i| [(i.start(), 1), (i.end() + 1, -1)])
+            .collect::<Vec<_>>();
+        events.sort();
+
+        let mut shared = IntervalSet::new();
+        let mut count = 0;
+        let mut start = 0;
+        for (pos, delta) in events {
+            if count < 2 && count + delta >= 2 {
+                start = pos;
+            } else if count >= 2 && count + delta < 2 {
+                shared.insert(Interval::new(start, pos - 1));
+            }
+            count += delta;
+        }
+        shared
+    }
+
+    pub fn print(&self) {
+        let join = |v: &[Interval<i32>]| {
+            v.iter()
+                .map(|i| i.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        println!("Assigned sections: {}", join(self.assigned.intervals()));
+        println!("Uncovered sections: {}", join(&self.uncovered));
+        println!(
+            "Sections shared by several elves: {}",
+            join(self.shared.intervals())
+        );
+        self.redundant.iter().enumerate().for_each(|(i, r)| {
+            if let Some(r) = r {
+                println!("Line {}: sections {} are assigned twice", i + 1, r);
+            }
+        });
+    }
+}
+
+fn audit(path: &str) -> Coverage {
+    Coverage::new(&read_pairs(path))
 }
 
 fn run_both(path: &str) -> (i32, i32) {
@@ -38,6 +113,7 @@ fn run_both(path: &str) -> (i32, i32) {
 fn main() {
     let (total_score, total_score_b) = run_both("example.txt");
     println!("The example score is: {} {}", total_score, total_score_b);
+    audit("example.txt").print();
 
     let (total_score, total_score_b) = run_both("input.txt");
     println!("The total score is: {} {}", total_score, total_score_b);
@@ -51,6 +127,25 @@ mod tests {
     fn aoc_test() {
         let (total_score, total_score_b) = run_both("input.txt");
         assert_eq!(total_score, 538);
-        assert_eq!(total_score_b, 2567);
+        assert_eq!(total_score_b, 792);
+    }
+
+    #[test]
+    fn coverage_test() {
+        let coverage = audit("example.txt");
+        assert_eq!(coverage.assigned.intervals(), [Interval::new(2, 9)]);
+        assert!(coverage.uncovered.is_empty());
+        assert_eq!(coverage.shared.intervals(), [Interval::new(2, 8)]);
+        assert_eq!(
+            coverage.redundant,
+            [
+                None,
+                None,
+                Some(Interval::new(7, 7)),
+                Some(Interval::new(3, 7)),
+                Some(Interval::new(6, 6)),
+                Some(Interval::new(4, 6)),
+            ]
+        );
     }
 }
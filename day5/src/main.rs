@@ -1,93 +1,618 @@
+use std::fmt;
 use std::fs::File;
-use std::io::{BufRead, BufReader, Lines};
+use std::io::{BufRead, BufReader};
+use std::thread;
+use std::time::Duration;
 
-#[derive(Clone, Default, Debug)]
-struct State {
-    over_9000: bool,
-    stacks: [Vec<char>; 9],
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Move {
+    count: usize,
+    from: usize,
+    to: usize,
 }
 
-impl State {
-    pub fn new(over_9000: bool) -> Self {
-        Self {
-            over_9000,
-            ..Default::default()
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "move {} from {} to {}",
+            self.count,
+            self.from + 1,
+            self.to + 1
+        )
+    }
+}
+
+/// A parsed puzzle input: the initial stacks and the moves to apply to them.
+#[derive(Clone, Debug)]
+struct Program {
+    stacks: Vec<Vec<String>>,
+    moves: Vec<Move>,
+}
+
+/// Describes how a crane rearranges the crates it lifts during a single move.
+trait CraneModel: fmt::Debug {
+    /// For every crate placed onto the destination stack (bottom to top),
+    /// returns the index of that crate within the lifted crates (bottom to top).
+    fn arrangement(&self, count: usize) -> Vec<usize>;
+}
+
+/// Moves one crate at a time, reversing their order.
+#[derive(Copy, Clone, Debug)]
+struct CrateMover9000;
+
+/// Moves all crates at once, keeping their order.
+#[derive(Copy, Clone, Debug)]
+struct CrateMover9001;
+
+/// Lifts at most `capacity` crates at once, so large moves are split into
+/// several batches that each keep their order.
+#[derive(Copy, Clone, Debug)]
+struct LimitedCrane {
+    capacity: usize,
+}
+
+/// Like [`LimitedCrane`], but every other batch gets turned upside down.
+#[derive(Copy, Clone, Debug)]
+struct FlippingCrane {
+    capacity: usize,
+}
+
+impl CraneModel for CrateMover9000 {
+    fn arrangement(&self, count: usize) -> Vec<usize> {
+        (0..count).rev().collect()
+    }
+}
+
+impl CraneModel for CrateMover9001 {
+    fn arrangement(&self, count: usize) -> Vec<usize> {
+        (0..count).collect()
+    }
+}
+
+/// Splits the lifted crates into batches starting from the top, optionally
+/// reversing every other batch.
+fn batch_arrangement(count: usize, capacity: usize, flip: bool) -> Vec<usize> {
+    (0..count)
+        .collect::<Vec<_>>()
+        .rchunks(capacity)
+        .enumerate()
+        .flat_map(|(i, batch)| {
+            let mut batch = batch.to_vec();
+            if flip && i % 2 == 1 {
+                batch.reverse();
+            }
+            batch
+        })
+        .collect()
+}
+
+impl LimitedCrane {
+    pub fn new(capacity: usize) -> Self {
+        assert!(
+            capacity > 0,
+            "A crane has to be able to lift at least one crate"
+        );
+        Self { capacity }
+    }
+}
+
+impl CraneModel for LimitedCrane {
+    fn arrangement(&self, count: usize) -> Vec<usize> {
+        batch_arrangement(count, self.capacity, false)
+    }
+}
+
+impl FlippingCrane {
+    pub fn new(capacity: usize) -> Self {
+        assert!(
+            capacity > 0,
+            "A crane has to be able to lift at least one crate"
+        );
+        Self { capacity }
+    }
+}
+
+impl CraneModel for FlippingCrane {
+    fn arrangement(&self, count: usize) -> Vec<usize> {
+        batch_arrangement(count, self.capacity, true)
+    }
+}
+
+#[derive(Clone, Debug)]
+struct State<'a> {
+    crane: &'a dyn CraneModel,
+    stacks: Vec<Vec<String>>,
+}
+
+/// Splits a diagram row into its crates, returning the column span
+/// (including the brackets) and the label of every crate.
+fn parse_crates(row: &str, line: usize) -> Result<Vec<([usize; 2], String)>, String> {
+    let chars = row.chars().collect::<Vec<_>>();
+    let mut ret = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            ' ' => i += 1,
+            '[' => {
+                let len = chars[i..].iter().position(|c| *c == ']').ok_or(format!(
+                    "Line {}: Unterminated crate in column {}",
+                    line,
+                    i + 1
+                ))?;
+                if len == 1 {
+                    return Err(format!("Line {}: Empty crate in column {}", line, i + 1));
+                }
+                ret.push(([i, i + len], chars[i + 1..i + len].iter().collect()));
+                i += len + 1;
+            }
+            c => {
+                return Err(format!(
+                    "Line {}: Unexpected character '{}' in column {}",
+                    line,
+                    c,
+                    i + 1
+                ))
+            }
+        }
+    }
+    Ok(ret)
+}
+
+/// Reads the stack numbers from the diagram footer, returning the column
+/// span of each number in order.
+fn parse_footer(footer: &str, line: usize) -> Result<Vec<[usize; 2]>, String> {
+    let chars = footer.chars().collect::<Vec<_>>();
+    let mut ret: Vec<[usize; 2]> = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] == ' ' {
+            i += 1;
+            continue;
+        }
+        let len = chars[i..].iter().take_while(|c| **c != ' ').count();
+        let word = chars[i..i + len].iter().collect::<String>();
+        let id = word
+            .parse::<usize>()
+            .map_err(|_| format!("Line {}: Invalid stack number: {}", line, word))?;
+        if id != ret.len() + 1 {
+            return Err(format!(
+                "Line {}: Expected stack number {} but found {}",
+                line,
+                ret.len() + 1,
+                id
+            ));
         }
+        ret.push([i, i + len - 1]);
+        i += len;
     }
+    if ret.is_empty() {
+        return Err(format!("Line {}: Missing stack numbers", line));
+    }
+    Ok(ret)
+}
 
-    pub fn read_data(mut self, mut iter:Lines<BufReader<File>>) -> Self {
-        let line = iter.next().unwrap().unwrap();
-        if line.trim().len() > 0 {
-            line.as_bytes().chunks(4).enumerate().for_each(|(i,s)| {
-                if s[0] == b'[' {
-                    self.stacks[i].push(s[1] as char);
-                };
-            });
-            self.read_data(iter)
-        } else {
-            self.stacks.iter_mut().for_each(|s| s.reverse());
-            self.eval(iter)
-        }
-    }
-
-    pub fn eval(mut self, mut iter:Lines<BufReader<File>>) -> Self {
-        if let Some(line) = iter.next() {
-            match line.unwrap().split(" ")
-                .filter_map(|w| w.parse::<usize>().ok())
-                .collect::<Vec<usize>>().as_slice() {
-                [count, from, to] => {
-                    let mut tmp:Vec<char> = Vec::with_capacity(*count);
-                    for _ in 0..*count {
-                        tmp.push(self.stacks[*from-1].pop().unwrap());
-                    }
-                    if self.over_9000 { tmp.reverse(); }
-                    tmp.iter().for_each(|c| self.stacks[*to-1].push(*c));
-                },
-                _ => panic!("Invalid line")
+fn parse_diagram(lines: &[String]) -> Result<Vec<Vec<String>>, String> {
+    let (footer, rows) = lines
+        .split_last()
+        .ok_or("Missing crate diagram".to_string())?;
+    let columns = parse_footer(footer, lines.len())?;
+    let mut stacks: Vec<Vec<String>> = vec![vec![]; columns.len()];
+
+    for (i, row) in rows.iter().enumerate().rev() {
+        let line = i + 1;
+        let height = rows.len() - 1 - i;
+        for ([start, end], label) in parse_crates(row, line)? {
+            let stack = columns
+                .iter()
+                .position(|c| c[0] <= end && start <= c[1])
+                .ok_or(format!(
+                    "Line {}: Crate [{}] isn't aligned with any stack",
+                    line, label
+                ))?;
+            if stacks[stack].len() != height {
+                return Err(format!(
+                    "Line {}: Crate [{}] on stack {} isn't resting on another crate",
+                    line,
+                    label,
+                    stack + 1
+                ));
             }
-            self.eval(iter)
+            stacks[stack].push(label);
+        }
+    }
+    Ok(stacks)
+}
+
+fn parse_move(text: &str, stack_count: usize, line: usize) -> Result<Move, String> {
+    match text.split(' ').collect::<Vec<_>>().as_slice() {
+        ["move", count, "from", from, "to", to] => {
+            let parse = |v: &str| {
+                v.parse::<usize>()
+                    .map_err(|_| format!("Line {}: Invalid number: {}", line, v))
+            };
+            let stack_index = |v: &str| {
+                let id = parse(v)?;
+                if id == 0 || id > stack_count {
+                    Err(format!(
+                        "Line {}: Move references missing stack {}",
+                        line, id
+                    ))
+                } else {
+                    Ok(id - 1)
+                }
+            };
+            Ok(Move {
+                count: parse(count)?,
+                from: stack_index(from)?,
+                to: stack_index(to)?,
+            })
+        }
+        _ => Err(format!("Line {}: Invalid move: {}", line, text)),
+    }
+}
+
+impl Program {
+    pub fn parse(mut iter: impl Iterator<Item = String>) -> Result<Self, String> {
+        let diagram = iter
+            .by_ref()
+            .take_while(|line| !line.trim().is_empty())
+            .collect::<Vec<_>>();
+        let stacks = parse_diagram(&diagram)?;
+        let moves = iter
+            .enumerate()
+            .filter(|(_, text)| !text.is_empty())
+            .map(|(i, text)| parse_move(&text, stacks.len(), diagram.len() + 2 + i))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { stacks, moves })
+    }
+}
+
+impl<'a> State<'a> {
+    pub fn new(crane: &'a dyn CraneModel, stacks: Vec<Vec<String>>) -> Self {
+        Self { crane, stacks }
+    }
+
+    pub fn apply(&mut self, m: &Move) -> Result<(), String> {
+        let height = self.stacks[m.from].len();
+        if height < m.count {
+            return Err(format!(
+                "Can't move {} crates from stack {} which only holds {}",
+                m.count,
+                m.from + 1,
+                height
+            ));
+        }
+        let mut lifted = self.stacks[m.from].split_off(height - m.count);
+        let placed = self
+            .crane
+            .arrangement(m.count)
+            .into_iter()
+            .map(|i| std::mem::take(&mut lifted[i]))
+            .collect::<Vec<_>>();
+        self.stacks[m.to].extend(placed);
+        Ok(())
+    }
+
+    /// Reverts a move previously applied with [`State::apply`].
+    pub fn undo(&mut self, m: &Move) {
+        let height = self.stacks[m.to].len();
+        let placed = self.stacks[m.to].split_off(height - m.count);
+        let mut lifted = vec![String::new(); m.count];
+        self.crane
+            .arrangement(m.count)
+            .into_iter()
+            .zip(placed)
+            .for_each(|(i, label)| lifted[i] = label);
+        self.stacks[m.from].extend(lifted);
+    }
+
+    pub fn eval(&mut self, moves: &[Move]) -> Result<(), String> {
+        for (i, m) in moves.iter().enumerate() {
+            self.apply(m)
+                .map_err(|e| format!("Move {}: {}", i + 1, e))?;
+        }
+        Ok(())
+    }
+
+    pub fn result(&self) -> String {
+        self.stacks
+            .iter()
+            .filter_map(|s| s.last())
+            .map(|s| s.as_str())
+            .collect()
+    }
+
+    /// Draws the stacks in the same format as the puzzle input, so the
+    /// output can be parsed again by [`Program::parse`].
+    pub fn render(&self) -> String {
+        let width = self
+            .stacks
+            .iter()
+            .flatten()
+            .map(|label| label.chars().count() + 2)
+            .max()
+            .unwrap_or(3)
+            .max(3);
+        let height = self.stacks.iter().map(|s| s.len()).max().unwrap_or(0);
+        let mut lines = (0..height)
+            .rev()
+            .map(|y| {
+                let row = self
+                    .stacks
+                    .iter()
+                    .map(|s| match s.get(y) {
+                        Some(label) => format!("{:<width$}", format!("[{}]", label)),
+                        None => " ".repeat(width),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                row.trim_end().to_string()
+            })
+            .collect::<Vec<_>>();
+        let footer = (1..=self.stacks.len())
+            .map(|i| {
+                format!(
+                    "{:<width$}",
+                    format!("{}{}", " ".repeat((width - 1) / 2), i)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        lines.push(footer.trim_end().to_string());
+        lines.join("\n")
+    }
+}
+
+/// Steps through a program one move at a time, in either direction.
+#[derive(Clone, Debug)]
+struct Simulator<'a> {
+    program: &'a Program,
+    state: State<'a>,
+    step: usize,
+}
+
+impl<'a> Simulator<'a> {
+    pub fn new(program: &'a Program, crane: &'a dyn CraneModel) -> Self {
+        Self {
+            program,
+            state: State::new(crane, program.stacks.clone()),
+            step: 0,
+        }
+    }
+
+    pub fn state(&self) -> &State<'a> {
+        &self.state
+    }
+
+    /// Applies the next move, returning `false` once the program is finished.
+    pub fn step_forward(&mut self) -> Result<bool, String> {
+        if let Some(m) = self.program.moves.get(self.step) {
+            self.state
+                .apply(m)
+                .map_err(|e| format!("Move {}: {}", self.step + 1, e))?;
+            self.step += 1;
+            Ok(true)
         } else {
-            self
+            Ok(false)
+        }
+    }
+
+    /// Reverts the last move, returning `false` when already at the start.
+    pub fn step_back(&mut self) -> bool {
+        if self.step == 0 {
+            return false;
+        }
+        self.step -= 1;
+        self.state.undo(&self.program.moves[self.step]);
+        true
+    }
+
+    pub fn seek(&mut self, step: usize) -> Result<(), String> {
+        while self.step > step {
+            self.step_back();
         }
+        while self.step < step && self.step_forward()? {}
+        Ok(())
     }
 
-    pub fn result(mut self) -> String {
-        self.stacks.map(|mut s| s.pop().unwrap_or(' ').to_string()).join("")
+    /// Renders the initial diagram followed by one frame per move.
+    pub fn animate(&mut self) -> Result<Vec<String>, String> {
+        self.seek(0)?;
+        let mut frames = vec![format!("Start\n\n{}", self.state().render())];
+        while self.step_forward()? {
+            let m = &self.program.moves[self.step - 1];
+            frames.push(format!(
+                "Step {}/{}: {}\n\n{}",
+                self.step,
+                self.program.moves.len(),
+                m,
+                self.state().render()
+            ));
+        }
+        Ok(frames)
     }
 }
 
-fn run(path: &str) -> String {
-    let file = File::open(path).unwrap();
-    let reader = BufReader::new(file).lines();
-    State::new(false).read_data(reader).result()
+fn read_lines(path: &str) -> Result<impl Iterator<Item = String>, String> {
+    let file = File::open(path).map_err(|e| format!("Couldn't open {}: {}", path, e))?;
+    Ok(BufReader::new(file).lines().map(|line| line.unwrap()))
+}
+
+fn execute(program: &Program, crane: &dyn CraneModel) -> Result<String, String> {
+    let mut state = State::new(crane, program.stacks.clone());
+    state.eval(&program.moves)?;
+    Ok(state.result())
+}
+
+fn run(program: &Program) -> Result<String, String> {
+    execute(program, &CrateMover9000)
+}
+
+fn run_two(program: &Program) -> Result<String, String> {
+    execute(program, &CrateMover9001)
 }
 
-fn run_two(path: &str) -> String {
-    let file = File::open(path).unwrap();
-    let reader = BufReader::new(file).lines();
-    State::new(true).read_data(reader).result()
+fn run_both(path: &str) -> Result<(String, String), String> {
+    let program = Program::parse(read_lines(path)?)?;
+    Ok((run(&program)?, run_two(&program)?))
 }
 
-fn run_both(path: &str) -> (String, String) {
-    (run(path), run_two(path))
+fn run_with(path: &str, crane: &dyn CraneModel) -> Result<String, String> {
+    execute(&Program::parse(read_lines(path)?)?, crane)
+}
+
+fn play_animation(path: &str, crane: &dyn CraneModel) -> Result<(), String> {
+    let program = Program::parse(read_lines(path)?)?;
+    for frame in Simulator::new(&program, crane).animate()? {
+        // Clear the terminal and move the cursor back to the top left
+        print!("\x1b[2J\x1b[H");
+        println!("{}", frame);
+        thread::sleep(Duration::from_millis(250));
+    }
+    Ok(())
 }
 
 fn main() {
-    let (total_score, total_score_b) = run_both("example.txt");
-    println!("The example score is: {} {}", total_score, total_score_b);
+    if std::env::args().any(|arg| arg == "--animate") {
+        if let Err(e) = play_animation("example.txt", &CrateMover9001) {
+            println!("Error: {}", e);
+        }
+        return;
+    }
+
+    match run_both("example.txt") {
+        Ok((total_score, total_score_b)) => {
+            println!("The example score is: {} {}", total_score, total_score_b)
+        }
+        Err(e) => println!("Error: {}", e),
+    }
+
+    match run_both("input.txt") {
+        Ok((total_score, total_score_b)) => {
+            println!("The score is: {} {}", total_score, total_score_b)
+        }
+        Err(e) => println!("Error: {}", e),
+    }
 
-    let (total_score, total_score_b) = run_both("input.txt");
-    println!("The score is: {} {}", total_score, total_score_b);
+    let cranes: [&dyn CraneModel; 2] = [&LimitedCrane::new(3), &FlippingCrane::new(3)];
+    for crane in cranes {
+        match run_with("input.txt", crane) {
+            Ok(result) => println!("{:?}: {}", crane, result),
+            Err(e) => println!("Error: {}", e),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn eval_str(s: &str) -> Result<String, String> {
+        let program = Program::parse(s.lines().map(|l| l.to_string()))?;
+        run(&program)
+    }
+
     #[test]
     fn aoc_test() {
-        let (total_score, total_score_b) = run_both("input.txt");
+        let (total_score, total_score_b) = run_both("input.txt").unwrap();
         assert_eq!(total_score.trim(), "WSFTMRHPP");
         assert_eq!(total_score_b.trim(), "GSLCMFBRP");
     }
+
+    #[test]
+    fn diagram_test() {
+        let wide = [
+            "                                        [K1]",
+            "[A1] [B1] [C1] [D1] [E1] [F1] [G1] [H1] [I1] [J1]",
+            " 1    2    3    4    5    6    7    8    9    10",
+            "",
+            "move 2 from 9 to 10",
+        ];
+        assert_eq!(eval_str(&wide.join("\n")).unwrap(), "A1B1C1D1E1F1G1H1I1");
+
+        let diagram = "[A] [B]\n 1   2\n\n";
+        assert!(eval_str(&format!("{}move 1 from 1 to 3", diagram))
+            .unwrap_err()
+            .contains("missing stack 3"));
+        assert!(eval_str(&format!("{}move 2 from 1 to 2", diagram))
+            .unwrap_err()
+            .contains("only holds 1"));
+        assert!(eval_str(&format!(
+            "{}move 1 from 1 to 2\nmove 1 from 1 to 2",
+            diagram
+        ))
+        .unwrap_err()
+        .starts_with("Move 2:"));
+        assert!(eval_str("  [A]\n 1   2\n\n")
+            .unwrap_err()
+            .contains("isn't aligned"));
+        assert!(eval_str("[A]\n    [B]\n 1   2\n\n")
+            .unwrap_err()
+            .contains("isn't resting"));
+    }
+
+    #[test]
+    fn simulator_test() {
+        let program = Program::parse(read_lines("example.txt").unwrap()).unwrap();
+        let diagram = std::fs::read_to_string("example.txt").unwrap();
+        let diagram = diagram.split("\n\n").next().unwrap();
+
+        let mut sim = Simulator::new(&program, &CrateMover9000);
+        assert_eq!(sim.state().render(), diagram);
+        sim.seek(2).unwrap();
+        assert_eq!(
+            sim.state().render(),
+            "        [Z]\n        [N]\n    [C] [D]\n    [M] [P]\n 1   2   3"
+        );
+        assert!(sim.step_back());
+        sim.seek(program.moves.len()).unwrap();
+        assert_eq!(sim.state().result(), "CMZ");
+        assert!(!sim.step_forward().unwrap());
+        sim.seek(0).unwrap();
+        assert_eq!(sim.state().render(), diagram);
+        assert_eq!(sim.animate().unwrap().len(), program.moves.len() + 1);
+
+        let rendered = Simulator::new(&program, &CrateMover9001).animate().unwrap();
+        assert!(rendered[4].starts_with("Step 4/4: move 1 from 1 to 2"));
+        let reparsed = Program::parse(rendered[4].lines().skip(2).map(|l| l.to_string())).unwrap();
+        assert_eq!(State::new(&CrateMover9001, reparsed.stacks).result(), "MCD");
+    }
+
+    #[test]
+    fn crane_model_test() {
+        assert_eq!(LimitedCrane::new(2).arrangement(5), [3, 4, 1, 2, 0]);
+        assert_eq!(FlippingCrane::new(2).arrangement(5), [3, 4, 2, 1, 0]);
+
+        let program = Program::parse(read_lines("input.txt").unwrap()).unwrap();
+        let cranes: [&dyn CraneModel; 4] = [
+            &CrateMover9000,
+            &CrateMover9001,
+            &LimitedCrane::new(3),
+            &FlippingCrane::new(3),
+        ];
+        for crane in cranes {
+            let mut sim = Simulator::new(&program, crane);
+            sim.seek(program.moves.len()).unwrap();
+            sim.seek(0).unwrap();
+            assert_eq!(sim.state().stacks, program.stacks);
+        }
+        assert_eq!(
+            execute(&program, &LimitedCrane::new(1)).unwrap(),
+            "WSFTMRHPP"
+        );
+        assert_eq!(
+            execute(&program, &LimitedCrane::new(usize::MAX)).unwrap(),
+            "GSLCMFBRP"
+        );
+    }
+
+    #[test]
+    fn long_program_test() {
+        let moves = (0..500000)
+            .map(|i| format!("move 1 from {} to {}", 1 + i % 2, 2 - i % 2))
+            .collect::<Vec<_>>();
+        let program = format!("[A] [B]\n 1   2\n\n{}", moves.join("\n"));
+        assert_eq!(eval_str(&program).unwrap(), "AB");
+    }
 }
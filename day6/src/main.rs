@@ -1,29 +1,144 @@
-use std::fs;
-use std::collections::HashSet;
-
-fn run(path: &str, len: usize) -> i32 {
-    let txt = fs::read_to_string(path).unwrap();
-    let mut acc = std::iter::repeat(' ').take(len).collect::<Vec<_>>();
-    for (i, c) in txt.chars().into_iter().enumerate() {
-        acc.push(c);
-        acc.remove(0);
-        if i >= len && acc.iter().collect::<HashSet<_>>().len() == len {
-            return (1 + i) as i32;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+
+/// Tracks the run of distinct bytes ending at the most recently pushed byte.
+///
+/// Only the last position every byte value was seen at is remembered, so a
+/// stream can be processed in a single pass without any buffering.
+#[derive(Clone, Debug)]
+struct DistinctWindow {
+    last_seen: [usize; 256],
+    start: usize,
+    pos: usize,
+}
+
+impl DistinctWindow {
+    pub fn new() -> Self {
+        Self {
+            last_seen: [0; 256],
+            start: 0,
+            pos: 0,
+        }
+    }
+
+    /// Adds the next byte and returns the length of the distinct run ending with it.
+    pub fn push(&mut self, b: u8) -> usize {
+        self.pos += 1;
+        self.start = self.start.max(self.last_seen[b as usize]);
+        self.last_seen[b as usize] = self.pos;
+        self.pos - self.start
+    }
+
+    /// Forgets everything before the current position.
+    pub fn reset(&mut self) {
+        self.start = self.pos;
+    }
+}
+
+/// Iterator over every position where the preceding `len` bytes are distinct,
+/// given as the number of bytes read up to that point.
+struct Markers<R: Read> {
+    bytes: io::Bytes<BufReader<R>>,
+    window: DistinctWindow,
+    len: usize,
+    overlapping: bool,
+}
+
+impl<R: Read> Markers<R> {
+    pub fn new(reader: R, len: usize) -> Self {
+        Self {
+            bytes: BufReader::new(reader).bytes(),
+            window: DistinctWindow::new(),
+            len,
+            overlapping: true,
+        }
+    }
+
+    /// Only yields markers that don't share any bytes with the previous
+    /// one, which splits a stream into consecutive packets.
+    pub fn packets(mut self) -> Self {
+        self.overlapping = false;
+        self
+    }
+}
+
+impl<R: Read> Iterator for Markers<R> {
+    type Item = io::Result<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for b in self.bytes.by_ref() {
+            let b = match b {
+                Ok(b) => b,
+                Err(e) => return Some(Err(e)),
+            };
+            if self.window.push(b) >= self.len {
+                if !self.overlapping {
+                    self.window.reset();
+                }
+                return Some(Ok(self.window.pos));
+            }
         }
+        None
     }
-    panic!("Couldn't find a sequence with 4 unique chars");
 }
 
-fn run_both(path: &str) -> (i32, i32) {
-    (run(path, 4), run(path, 14))
+/// Returns the position of the first marker of length `len` in the stream.
+fn find_marker(reader: impl Read, len: usize) -> io::Result<Option<usize>> {
+    Markers::new(reader, len).next().transpose()
+}
+
+/// Finds the longest run of distinct bytes in the stream, returned as the
+/// offset of its first byte and its length. Earlier runs win ties.
+fn longest_distinct_run(reader: impl Read) -> io::Result<Option<(usize, usize)>> {
+    let mut window = DistinctWindow::new();
+    let mut best: Option<(usize, usize)> = None;
+    for b in BufReader::new(reader).bytes() {
+        let len = window.push(b?);
+        if best.is_none_or(|(_, best_len)| len > best_len) {
+            best = Some((window.pos - len, len));
+        }
+    }
+    Ok(best)
+}
+
+fn run(path: &str, len: usize) -> io::Result<Option<usize>> {
+    find_marker(File::open(path)?, len)
+}
+
+fn run_both(path: &str) -> io::Result<(Option<usize>, Option<usize>)> {
+    Ok((run(path, 4)?, run(path, 14)?))
 }
 
 fn main() {
-    let (total_score, total_score_b) = run_both("example.txt");
-    println!("The example score is: {} {}", total_score, total_score_b);
+    match run_both("example.txt") {
+        Ok((total_score, total_score_b)) => {
+            println!(
+                "The example score is: {:?} {:?}",
+                total_score, total_score_b
+            )
+        }
+        Err(e) => println!("Error: {}", e),
+    }
 
-    let (total_score, total_score_b) = run_both("input.txt");
-    println!("The score is: {} {}", total_score, total_score_b);
+    match run_both("input.txt") {
+        Ok((total_score, total_score_b)) => {
+            println!("The score is: {:?} {:?}", total_score, total_score_b)
+        }
+        Err(e) => println!("Error: {}", e),
+    }
+
+    let stats = File::open("input.txt").and_then(|file| {
+        let packets = Markers::new(file, 4).packets().count();
+        Ok((packets, longest_distinct_run(File::open("input.txt")?)?))
+    });
+    match stats {
+        Ok((packets, Some((start, len)))) => println!(
+            "The input holds {} packets, its longest distinct run has {} characters at offset {}",
+            packets, len, start
+        ),
+        Ok((packets, None)) => println!("The input holds {} packets", packets),
+        Err(e) => println!("Error: {}", e),
+    }
 }
 
 #[cfg(test)]
@@ -32,8 +147,30 @@ mod tests {
 
     #[test]
     fn aoc_test() {
-        let (total_score, total_score_b) = run_both("input.txt");
-        assert_eq!(total_score, 1282);
-        assert_eq!(total_score_b, 3513);
+        let (total_score, total_score_b) = run_both("input.txt").unwrap();
+        assert_eq!(total_score, Some(1282));
+        assert_eq!(total_score_b, Some(3513));
+    }
+
+    #[test]
+    fn marker_test() {
+        let signal = "mjqjpqmgbljsphdztnvjfqwrcgsmlb".as_bytes();
+        assert_eq!(find_marker(signal, 4).unwrap(), Some(7));
+        assert_eq!(find_marker(signal, 14).unwrap(), Some(19));
+        assert_eq!(find_marker("abcd".as_bytes(), 4).unwrap(), Some(4));
+        assert_eq!(find_marker("abcabc".as_bytes(), 4).unwrap(), None);
+    }
+
+    #[test]
+    fn statistics_test() {
+        let signal = "abcabcdaab".as_bytes();
+        let markers = Markers::new(signal, 3).collect::<io::Result<Vec<_>>>();
+        assert_eq!(markers.unwrap(), [3, 4, 5, 6, 7, 8]);
+        let packets = Markers::new(signal, 3)
+            .packets()
+            .collect::<io::Result<Vec<_>>>();
+        assert_eq!(packets.unwrap(), [3, 6]);
+        assert_eq!(longest_distinct_run(signal).unwrap(), Some((3, 4)));
+        assert_eq!(longest_distinct_run("".as_bytes()).unwrap(), None);
     }
 }
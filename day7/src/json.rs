@@ -0,0 +1,175 @@
+use std::fmt::{self, Display};
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// The subset of JSON needed to store a filesystem: only non-negative
+/// integers are supported as numbers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Json {
+    Number(usize),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+impl Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number(n) => write!(f, "{}", n),
+            Self::String(s) => write_string(f, s),
+            Self::Array(a) => {
+                write!(f, "[")?;
+                for (i, v) in a.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", v)?;
+                }
+                write!(f, "]")
+            }
+            Self::Object(o) => {
+                write!(f, "{{")?;
+                for (i, (k, v)) in o.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, k)?;
+                    write!(f, ":{}", v)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn skip_whitespace(iter: &mut Peekable<Chars>) {
+    while iter.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+fn expect(iter: &mut Peekable<Chars>, expected: char) -> Result<(), String> {
+    skip_whitespace(iter);
+    match iter.next() {
+        Some(c) if c == expected => Ok(()),
+        Some(c) => Err(format!("Expected '{}' but found '{}'", expected, c)),
+        None => Err(format!("Expected '{}' but the input ended", expected)),
+    }
+}
+
+fn read_string(iter: &mut Peekable<Chars>) -> Result<String, String> {
+    expect(iter, '"')?;
+    let mut ret = String::new();
+    loop {
+        match iter.next().ok_or("Unterminated string")? {
+            '"' => return Ok(ret),
+            '\\' => match iter.next().ok_or("Unterminated string")? {
+                '"' => ret.push('"'),
+                '\\' => ret.push('\\'),
+                '/' => ret.push('/'),
+                'b' => ret.push('\u{8}'),
+                'f' => ret.push('\u{c}'),
+                'n' => ret.push('\n'),
+                'r' => ret.push('\r'),
+                't' => ret.push('\t'),
+                'u' => {
+                    let hex = iter.by_ref().take(4).collect::<String>();
+                    let c = u32::from_str_radix(&hex, 16)
+                        .ok()
+                        .and_then(char::from_u32)
+                        .ok_or(format!("Invalid unicode escape: \\u{}", hex))?;
+                    ret.push(c);
+                }
+                c => return Err(format!("Invalid escape sequence: \\{}", c)),
+            },
+            c => ret.push(c),
+        }
+    }
+}
+
+fn read_value(iter: &mut Peekable<Chars>) -> Result<Json, String> {
+    skip_whitespace(iter);
+    match iter.peek() {
+        Some('"') => Ok(Json::String(read_string(iter)?)),
+        Some('[') => {
+            iter.next();
+            let mut ret = vec![];
+            skip_whitespace(iter);
+            if iter.next_if_eq(&']').is_some() {
+                return Ok(Json::Array(ret));
+            }
+            loop {
+                ret.push(read_value(iter)?);
+                skip_whitespace(iter);
+                match iter.next() {
+                    Some(',') => (),
+                    Some(']') => return Ok(Json::Array(ret)),
+                    _ => return Err("Expected ',' or ']' in array".to_string()),
+                }
+            }
+        }
+        Some('{') => {
+            iter.next();
+            let mut ret = vec![];
+            skip_whitespace(iter);
+            if iter.next_if_eq(&'}').is_some() {
+                return Ok(Json::Object(ret));
+            }
+            loop {
+                let key = read_string(iter)?;
+                expect(iter, ':')?;
+                ret.push((key, read_value(iter)?));
+                skip_whitespace(iter);
+                match iter.next() {
+                    Some(',') => skip_whitespace(iter),
+                    Some('}') => return Ok(Json::Object(ret)),
+                    _ => return Err("Expected ',' or '}' in object".to_string()),
+                }
+            }
+        }
+        Some(c) if c.is_ascii_digit() => {
+            let mut digits = String::new();
+            while let Some(c) = iter.next_if(|c| c.is_ascii_digit()) {
+                digits.push(c);
+            }
+            digits
+                .parse()
+                .map(Json::Number)
+                .map_err(|_| format!("Number out of range: {}", digits))
+        }
+        Some(c) => Err(format!("Unexpected character: '{}'", c)),
+        None => Err("Unexpected end of input".to_string()),
+    }
+}
+
+impl Json {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut iter = s.chars().peekable();
+        let ret = read_value(&mut iter)?;
+        skip_whitespace(&mut iter);
+        match iter.next() {
+            None => Ok(ret),
+            Some(c) => Err(format!("Trailing character after JSON value: '{}'", c)),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Self::Object(o) => o.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+}
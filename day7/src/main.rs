@@ -1,120 +1,819 @@
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+mod json;
+
+use json::Json;
 use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+type NodeId = usize;
+
+/// Most 64 bit words the table of `FileSystem::cleanup_plan` may take up,
+/// which is 8 MB.
+const CLEANUP_TABLE_WORDS: usize = 1 << 20;
 
 #[derive(Clone, Debug)]
 enum INode {
-    Dir { name: String, children: HashMap<String, INode> },
-    File { name: String, size: usize },
+    Dir {
+        name: String,
+        children: HashMap<String, NodeId>,
+    },
+    File {
+        name: String,
+        size: usize,
+    },
 }
 
 impl INode {
-    fn insert_rec(&mut self, path: &Vec<String>, i: usize, node: INode) {
-        if let INode::Dir{children, ..} = self {
-            if path.len() == i + 1 {
-                children.insert(path[i].clone(), node);
-            } else {
-                if let Some(inode) = children.get_mut(&path[i]) {
-                    inode.insert_rec(path, i+1, node);
-                } else {
-                    panic!("Couldn't find entry");
-                }
-            }
+    pub fn name(&self) -> &str {
+        match self {
+            INode::Dir { name, .. } | INode::File { name, .. } => name,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Node {
+    inode: INode,
+    parent: Option<NodeId>,
+    /// Total size of the file, or of everything below the directory.
+    size: usize,
+    /// Number of files at or below this node.
+    files: usize,
+    depth: usize,
+}
+
+/// Limits which entries show up in [`FileSystem::tree`] and [`FileSystem::du`].
+#[derive(Copy, Clone, Debug)]
+struct ReportFilter {
+    min_size: usize,
+    max_depth: usize,
+}
+
+impl Default for ReportFilter {
+    fn default() -> Self {
+        Self {
+            min_size: 0,
+            max_depth: usize::MAX,
+        }
+    }
+}
+
+/// Formats a size the way `du -h` does, rounding up to one decimal below 10.
+fn human_size(size: usize) -> String {
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < 4 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    let round = |value: f64, unit: usize| {
+        if unit > 0 && (value * 10.0).ceil() < 100.0 {
+            (value * 10.0).ceil() / 10.0
+        } else {
+            value.ceil()
+        }
+    };
+    value = round(value, unit);
+    // Rounding up can reach the next unit, like 1048575 becoming 1.0M
+    if value >= 1024.0 && unit < 4 {
+        unit += 1;
+        value = round(value / 1024.0, unit);
+    }
+    let suffix = ["", "K", "M", "G", "T"][unit];
+    if unit > 0 && value < 10.0 {
+        format!("{:.1}{}", value, suffix)
+    } else {
+        format!("{}{}", value, suffix)
+    }
+}
+
+/// Filesystem tree stored in an arena, where every node knows its parent and
+/// directory sizes are kept up to date whenever a file gets inserted.
+#[derive(Clone, Debug)]
+struct FileSystem {
+    nodes: Vec<Node>,
+}
+
+impl FileSystem {
+    pub const ROOT: NodeId = 0;
+
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![Node {
+                inode: INode::Dir {
+                    name: "".to_string(),
+                    children: HashMap::new(),
+                },
+                parent: None,
+                size: 0,
+                files: 0,
+                depth: 0,
+            }],
+        }
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes[id].parent
+    }
+
+    pub fn size(&self, id: NodeId) -> usize {
+        self.nodes[id].size
+    }
+
+    pub fn files(&self, id: NodeId) -> usize {
+        self.nodes[id].files
+    }
+
+    pub fn depth(&self, id: NodeId) -> usize {
+        self.nodes[id].depth
+    }
+
+    pub fn is_dir(&self, id: NodeId) -> bool {
+        matches!(self.nodes[id].inode, INode::Dir { .. })
+    }
+
+    pub fn child(&self, dir: NodeId, name: &str) -> Option<NodeId> {
+        match &self.nodes[dir].inode {
+            INode::Dir { children, .. } => children.get(name).copied(),
+            INode::File { .. } => None,
+        }
+    }
+
+    /// Entries of a directory, sorted by name.
+    pub fn children(&self, dir: NodeId) -> Vec<NodeId> {
+        let mut ret = match &self.nodes[dir].inode {
+            INode::Dir { children, .. } => children.values().copied().collect::<Vec<_>>(),
+            INode::File { .. } => vec![],
+        };
+        ret.sort_by(|a, b| self.nodes[*a].inode.name().cmp(self.nodes[*b].inode.name()));
+        ret
+    }
+
+    /// Looks up an absolute path like `/a/b`.
+    pub fn lookup(&self, path: &str) -> Option<NodeId> {
+        path.split('/')
+            .filter(|p| !p.is_empty())
+            .try_fold(Self::ROOT, |id, name| self.child(id, name))
+    }
+
+    pub fn path(&self, id: NodeId) -> String {
+        let mut parts = vec![];
+        let mut cur = Some(id);
+        while let Some(id) = cur {
+            parts.push(self.nodes[id].inode.name());
+            cur = self.nodes[id].parent;
+        }
+        parts.reverse();
+        if parts.len() == 1 {
+            "/".to_string()
         } else {
-            panic!("Found file in path");
+            parts.join("/")
         }
     }
 
-    pub fn insert_dir(&mut self, path: &Vec<String>) {
-        let node = INode::Dir{name: path[path.len()-1].clone(), children: HashMap::new() };
-        self.insert_rec(path, 0, node);
+    pub fn dirs(&self) -> impl Iterator<Item = NodeId> + '_ {
+        (0..self.nodes.len()).filter(|id| self.is_dir(*id))
     }
 
-    pub fn insert_file(&mut self, path: &Vec<String>, size: usize) {
-        let node = INode::File{name: path[path.len()-1].clone(), size };
-        self.insert_rec(path, 0, node);
+    /// Replaces `old` by `new` in the size of `id` and all of its ancestors.
+    fn propagate_size(&mut self, id: NodeId, old: usize, new: usize) {
+        let mut cur = Some(id);
+        while let Some(id) = cur {
+            let node = &mut self.nodes[id];
+            node.size = node.size - old + new;
+            cur = node.parent;
+        }
     }
 
-    pub fn size(&self) -> usize {
-        match self {
-            INode::File{size, ..} => *size,
-            INode::Dir{children, ..} => {
-                children.iter().fold(0, |acc, (_,i)| acc + i.size())
+    /// Adds a new child to `dir`. Every name gets checked here, so nothing
+    /// can escape its directory in `lookup` or `materialize`.
+    fn insert(&mut self, dir: NodeId, inode: INode) -> Result<NodeId, String> {
+        let name = inode.name().to_string();
+        if name.is_empty() || name.contains(['/', '\0']) || name == "." || name == ".." {
+            return Err(format!("Invalid file name: {:?}", name));
+        }
+        if !self.is_dir(dir) {
+            return Err(format!("Not a directory: {}", self.path(dir)));
+        }
+        let id = self.nodes.len();
+        let size = match &inode {
+            INode::File { size, .. } => *size,
+            INode::Dir { .. } => 0,
+        };
+        let is_file = matches!(inode, INode::File { .. });
+        self.nodes.push(Node {
+            inode,
+            parent: Some(dir),
+            size: 0,
+            files: 0,
+            depth: self.nodes[dir].depth + 1,
+        });
+        if let INode::Dir { children, .. } = &mut self.nodes[dir].inode {
+            children.insert(name, id);
+        }
+        self.propagate_size(id, 0, size);
+        if is_file {
+            let mut cur = Some(id);
+            while let Some(id) = cur {
+                self.nodes[id].files += 1;
+                cur = self.nodes[id].parent;
+            }
+        }
+        Ok(id)
+    }
+
+    /// Adds a directory to `dir`, or returns the existing one when a
+    /// directory gets listed more than once.
+    pub fn insert_dir(&mut self, dir: NodeId, name: &str) -> Result<NodeId, String> {
+        match self.child(dir, name) {
+            Some(id) if self.is_dir(id) => Ok(id),
+            Some(id) => Err(format!(
+                "{} changed from a file to a directory",
+                self.path(id)
+            )),
+            None => self.insert(
+                dir,
+                INode::Dir {
+                    name: name.to_string(),
+                    children: HashMap::new(),
+                },
+            ),
+        }
+    }
+
+    /// Adds a file to `dir`, or updates the size of the existing one when a
+    /// directory gets listed more than once.
+    pub fn insert_file(&mut self, dir: NodeId, name: &str, size: usize) -> Result<NodeId, String> {
+        match self.child(dir, name) {
+            Some(id) if self.is_dir(id) => Err(format!(
+                "{} changed from a directory to a file",
+                self.path(id)
+            )),
+            Some(id) => {
+                if let INode::File { size: old, .. } = &mut self.nodes[id].inode {
+                    *old = size;
+                }
+                self.propagate_size(id, self.nodes[id].size, size);
+                Ok(id)
             }
+            None => self.insert(
+                dir,
+                INode::File {
+                    name: name.to_string(),
+                    size,
+                },
+            ),
         }
     }
 
-    pub fn task_a(&self) -> usize {
-        if let INode::Dir{children, ..} = self {
-            let size = self.size();
-            let sum = children.iter().map(|(_,i)| i.task_a()).sum();
-            if size <= 100000 {
-                sum + size
+    fn tree_rec(&self, id: NodeId, prefix: &str, filter: &ReportFilter, out: &mut Vec<String>) {
+        if self.depth(id) >= filter.max_depth {
+            return;
+        }
+        let children = self
+            .children(id)
+            .into_iter()
+            .filter(|c| self.size(*c) >= filter.min_size)
+            .collect::<Vec<_>>();
+        for (i, child) in children.iter().enumerate() {
+            let last = i + 1 == children.len();
+            let name = self.nodes[*child].inode.name();
+            let (branch, indent) = if last {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+            if self.is_dir(*child) {
+                out.push(format!(
+                    "{}{}{}/ ({})",
+                    prefix,
+                    branch,
+                    name,
+                    self.size(*child)
+                ));
+                self.tree_rec(*child, &format!("{}{}", prefix, indent), filter, out);
             } else {
-                sum
+                out.push(format!(
+                    "{}{}{} ({})",
+                    prefix,
+                    branch,
+                    name,
+                    self.size(*child)
+                ));
             }
-        } else {
-            0
         }
     }
 
-    pub fn task_b_rec(&self, mut acc:Vec<usize>) -> Vec<usize> {
-        match self {
-            INode::Dir {children, ..} => {
-                acc.push(self.size());
-                children.iter().fold(acc, |acc, (_,i)| i.task_b_rec(acc))
-            },
-            _ => acc
-        }
-    }
-
-    pub fn task_b(&self) -> usize {
-        let total = 70000000;
-        let used = self.size();
-        let free = total - used;
-        let goal = 30000000 - free;
-        let sizes = self.task_b_rec(vec![]);
-        *sizes.iter().filter(|v| **v >= goal).min().unwrap()
-    }
-}
-
-fn run_both(path: &str) -> (usize, usize) {
-    let mut root = INode::Dir { name: "".to_string(), children: HashMap::new() };
-    let mut cwd = vec![];
-    let file = File::open(path).unwrap();
-    let reader = BufReader::new(file).lines();
-    reader.for_each(|line| {
-        let line = line.unwrap();
-        if line.starts_with("$ cd") {
-            if line == "$ cd /" {
-                cwd.clear();
-            } else if line == "$ cd .." {
-                cwd.pop();
+    /// Renders the filesystem like `tree`, with every entry sorted by name.
+    pub fn tree(&self, filter: &ReportFilter) -> String {
+        let mut out = vec![format!("/ ({})", self.size(Self::ROOT))];
+        self.tree_rec(Self::ROOT, "", filter, &mut out);
+        out.join("\n")
+    }
+
+    fn du_rec(&self, id: NodeId, filter: &ReportFilter, out: &mut Vec<String>) {
+        for child in self.children(id) {
+            if self.is_dir(child) {
+                self.du_rec(child, filter, out);
+            }
+        }
+        if self.depth(id) <= filter.max_depth && self.size(id) >= filter.min_size {
+            out.push(format!(
+                "{}\t{}\t{}\t{}",
+                human_size(self.size(id)),
+                self.files(id),
+                self.depth(id),
+                self.path(id)
+            ));
+        }
+    }
+
+    /// Lists every directory like `du -h`, followed by its file count and depth.
+    pub fn du(&self, filter: &ReportFilter) -> String {
+        let mut out = vec![];
+        self.du_rec(Self::ROOT, filter, &mut out);
+        out.join("\n")
+    }
+
+    /// Sum of the sizes of all directories holding at most `limit` bytes.
+    pub fn task_a(&self, limit: usize) -> usize {
+        self.dirs()
+            .map(|id| self.size(id))
+            .filter(|size| *size <= limit)
+            .sum()
+    }
+
+    /// How much has to be deleted so that `required` bytes are free on a
+    /// disk holding `capacity` bytes.
+    pub fn space_to_free(&self, capacity: usize, required: usize) -> Result<usize, String> {
+        let used = self.size(Self::ROOT);
+        if used > capacity {
+            return Err(format!(
+                "{} bytes don't fit on a {} byte disk",
+                used, capacity
+            ));
+        }
+        if required > capacity {
+            return Err(format!(
+                "{} bytes can never be free on a {} byte disk",
+                required, capacity
+            ));
+        }
+        Ok(required.saturating_sub(capacity - used))
+    }
+
+    /// Size of the smallest single directory that frees up enough space.
+    pub fn task_b(&self, capacity: usize, required: usize) -> Result<usize, String> {
+        let goal = self.space_to_free(capacity, required)?;
+        self.dirs()
+            .map(|id| self.size(id))
+            .filter(|v| *v >= goal)
+            .min()
+            .ok_or("No directory is large enough".to_string())
+    }
+
+    /// Lists all directories below `dir` in pre-order, together with the
+    /// index right after each directory's subtree.
+    fn preorder_dirs(&self, dir: NodeId, out: &mut Vec<(NodeId, usize)>) {
+        for child in self.children(dir) {
+            if self.is_dir(child) {
+                let i = out.len();
+                out.push((child, 0));
+                self.preorder_dirs(child, out);
+                out[i].1 = out.len();
+            }
+        }
+    }
+
+    /// Finds the directories to delete that free up enough space while
+    /// deleting as little as possible. Nested directories are never picked
+    /// together, so no file gets counted twice.
+    ///
+    /// This is a subset sum over the directories, which takes time and
+    /// memory in O(dirs * limit / 64) for a limit of the best single
+    /// directory's size. Once that table would exceed
+    /// `CLEANUP_TABLE_WORDS`, sizes are counted in blocks of a power of two
+    /// bytes like `du` does, so the plan may delete up to a block per
+    /// directory more than needed. With too many directories for even that,
+    /// the best single directory gets picked instead.
+    pub fn cleanup_plan(&self, capacity: usize, required: usize) -> Result<Vec<NodeId>, String> {
+        let goal = self.space_to_free(capacity, required)?;
+        if goal == 0 {
+            return Ok(vec![]);
+        }
+        let mut dirs = vec![];
+        self.preorder_dirs(Self::ROOT, &mut dirs);
+
+        // No plan can beat the best single directory, or deleting everything
+        let fallback = match dirs
+            .iter()
+            .map(|(id, _)| *id)
+            .filter(|id| self.size(*id) >= goal)
+            .min_by_key(|id| self.size(*id))
+        {
+            Some(id) => vec![id],
+            None => self
+                .children(Self::ROOT)
+                .into_iter()
+                .filter(|id| self.is_dir(*id))
+                .collect(),
+        };
+        let limit = fallback.iter().map(|id| self.size(*id)).sum::<usize>();
+        if limit < goal {
+            return Err("Deleting every directory doesn't free up enough space".to_string());
+        }
+
+        let words = |block: usize| (dirs.len() + 1) * (limit / block / 64 + 1);
+        let mut block = 1;
+        while words(block) > CLEANUP_TABLE_WORDS && block <= limit {
+            block *= 2;
+        }
+        let mut plan = match words(block) <= CLEANUP_TABLE_WORDS {
+            true => self.cleanup_blocks(&dirs, block, goal, limit),
+            false => None,
+        }
+        .unwrap_or(fallback);
+        plan.sort_by_key(|id| self.path(*id));
+        Ok(plan)
+    }
+
+    /// Solves the subset sum of `cleanup_plan` with every size counted in
+    /// whole blocks. Rounding sizes down and the goal up keeps the plan
+    /// valid, but rounding may also hide every plan that's within `limit`.
+    fn cleanup_blocks(
+        &self,
+        dirs: &[(NodeId, usize)],
+        block: usize,
+        goal: usize,
+        limit: usize,
+    ) -> Option<Vec<NodeId>> {
+        let blocks = |id: NodeId| self.size(id) / block;
+        let (goal, limit) = (goal.div_ceil(block), limit / block);
+
+        // reach[i] has bit s set if deleting directories from dirs[i..] can
+        // free exactly s blocks. Deleting dirs[i] skips its whole subtree.
+        let words = limit / 64 + 1;
+        let mut reach = vec![vec![0u64; words]; dirs.len() + 1];
+        reach[dirs.len()][0] = 1;
+        for (i, (id, end)) in dirs.iter().enumerate().rev() {
+            let mut cur = reach[i + 1].clone();
+            let size = blocks(*id);
+            let (word_shift, bit_shift) = (size / 64, size % 64);
+            for w in (word_shift..words).rev() {
+                let src = w - word_shift;
+                let mut bits = reach[*end][src] << bit_shift;
+                if bit_shift > 0 && src > 0 {
+                    bits |= reach[*end][src - 1] >> (64 - bit_shift);
+                }
+                cur[w] |= bits;
+            }
+            reach[i] = cur;
+        }
+        let has = |i: usize, s: usize| reach[i][s / 64] & (1 << (s % 64)) != 0;
+
+        let mut left = (goal..=limit).find(|s| has(0, *s))?;
+        let mut plan = vec![];
+        let mut i = 0;
+        while left > 0 {
+            if has(i + 1, left) {
+                i += 1;
             } else {
-                cwd.push(line[5..].to_string());
+                let (id, end) = dirs[i];
+                plan.push(id);
+                left -= blocks(id);
+                i = end;
             }
-        } else if line.starts_with("$ ") {
-            // Ignore
+        }
+        Some(plan)
+    }
+
+    fn node_json(&self, id: NodeId) -> Json {
+        let name = Json::String(self.nodes[id].inode.name().to_string());
+        match &self.nodes[id].inode {
+            INode::Dir { .. } => Json::Object(vec![
+                ("name".to_string(), name),
+                ("type".to_string(), Json::String("dir".to_string())),
+                (
+                    "children".to_string(),
+                    Json::Array(
+                        self.children(id)
+                            .into_iter()
+                            .map(|c| self.node_json(c))
+                            .collect(),
+                    ),
+                ),
+            ]),
+            INode::File { size, .. } => Json::Object(vec![
+                ("name".to_string(), name),
+                ("type".to_string(), Json::String("file".to_string())),
+                ("size".to_string(), Json::Number(*size)),
+            ]),
+        }
+    }
+
+    pub fn to_json(&self) -> Json {
+        self.node_json(Self::ROOT)
+    }
+
+    fn insert_json(&mut self, dir: NodeId, json: &Json) -> Result<(), String> {
+        let children = match json.get("children") {
+            Some(Json::Array(children)) => children,
+            _ => return Err("Directory without a list of children".to_string()),
+        };
+        for child in children {
+            let name = match child.get("name") {
+                Some(Json::String(name)) => name,
+                _ => return Err("Entry without a name".to_string()),
+            };
+            match child.get("type") {
+                Some(Json::String(t)) if t == "dir" => {
+                    let id = self.insert_dir(dir, name)?;
+                    self.insert_json(id, child)?;
+                }
+                Some(Json::String(t)) if t == "file" => match child.get("size") {
+                    Some(Json::Number(size)) => {
+                        self.insert_file(dir, name, *size)?;
+                    }
+                    _ => return Err(format!("File {} without a size", name)),
+                },
+                _ => return Err(format!("Entry {} has an unknown type", name)),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn from_json(json: &Json) -> Result<Self, String> {
+        let mut fs = Self::new();
+        fs.insert_json(Self::ROOT, json)?;
+        Ok(fs)
+    }
+
+    /// Recreates the filesystem below `dir`, using sparse files so that
+    /// even the puzzle input only takes up a few blocks on disk.
+    pub fn materialize(&self, dir: &Path) -> io::Result<()> {
+        for child in self.children(Self::ROOT) {
+            self.materialize_rec(child, dir)?;
+        }
+        Ok(())
+    }
+
+    fn materialize_rec(&self, id: NodeId, dir: &Path) -> io::Result<()> {
+        let path = dir.join(self.nodes[id].inode.name());
+        match &self.nodes[id].inode {
+            INode::Dir { .. } => {
+                fs::create_dir(&path)?;
+                for child in self.children(id) {
+                    self.materialize_rec(child, &path)?;
+                }
+            }
+            INode::File { size, .. } => File::create(&path)?.set_len(*size as u64)?,
+        }
+        Ok(())
+    }
+
+    /// Reads a real directory tree, ignoring anything that is neither a
+    /// regular file nor a directory.
+    pub fn scan(dir: &Path) -> io::Result<Self> {
+        let mut fs = Self::new();
+        fs.scan_rec(Self::ROOT, dir)?;
+        Ok(fs)
+    }
+
+    fn scan_rec(&mut self, id: NodeId, dir: &Path) -> io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            let meta = fs::symlink_metadata(entry.path())?;
+            if meta.is_dir() {
+                let child = self.insert_dir(id, &name).map_err(io::Error::other)?;
+                self.scan_rec(child, &entry.path())?;
+            } else if meta.is_file() {
+                self.insert_file(id, &name, meta.len() as usize)
+                    .map_err(io::Error::other)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn transcript_rec(&self, id: NodeId, out: &mut Vec<String>) {
+        out.push("$ ls".to_string());
+        let children = self.children(id);
+        for child in children.iter() {
+            match &self.nodes[*child].inode {
+                INode::Dir { name, .. } => out.push(format!("dir {}", name)),
+                INode::File { name, size } => out.push(format!("{} {}", size, name)),
+            }
+        }
+        for child in children.into_iter().filter(|c| self.is_dir(*c)) {
+            out.push(format!("$ cd {}", self.nodes[child].inode.name()));
+            self.transcript_rec(child, out);
+            out.push("$ cd ..".to_string());
+        }
+    }
+
+    /// Generates a terminal session that explores the whole filesystem,
+    /// in the same format as the puzzle input.
+    pub fn transcript(&self) -> Vec<String> {
+        let mut out = vec!["$ cd /".to_string()];
+        self.transcript_rec(Self::ROOT, &mut out);
+        out
+    }
+}
+
+/// Replays a terminal transcript, rebuilding the filesystem it explored.
+#[derive(Clone, Debug)]
+struct Shell {
+    fs: FileSystem,
+    cwd: NodeId,
+    listing: bool,
+}
+
+impl Shell {
+    pub fn new() -> Self {
+        Self {
+            fs: FileSystem::new(),
+            cwd: FileSystem::ROOT,
+            listing: false,
+        }
+    }
+
+    fn cd(&mut self, path: &str) -> Result<(), String> {
+        let mut cwd = if path.starts_with('/') {
+            FileSystem::ROOT
         } else {
-            let s = line.split(" ").collect::<Vec<_>>();
-            cwd.push(s[1].to_string());
-            if let Ok(size) = s[0].parse::<usize>() {
-                root.insert_file(&cwd, size);
-            } else {
-                root.insert_dir(&cwd);
+            self.cwd
+        };
+        for part in path.split('/').filter(|p| !p.is_empty()) {
+            cwd = match part {
+                "." => cwd,
+                ".." => self.fs.parent(cwd).unwrap_or(FileSystem::ROOT),
+                name => match self.fs.child(cwd, name) {
+                    Some(id) if self.fs.is_dir(id) => id,
+                    Some(_) => return Err(format!("Not a directory: {}", path)),
+                    None => return Err(format!("No such directory: {}", path)),
+                },
+            };
+        }
+        self.cwd = cwd;
+        Ok(())
+    }
+
+    fn list_entry(&mut self, line: &str) -> Result<(), String> {
+        let (info, name) = line
+            .split_once(' ')
+            .ok_or(format!("Invalid ls output: {}", line))?;
+        if info == "dir" {
+            self.fs.insert_dir(self.cwd, name)?;
+        } else {
+            let size = info
+                .parse::<usize>()
+                .map_err(|_| format!("Invalid file size: {}", info))?;
+            self.fs.insert_file(self.cwd, name, size)?;
+        }
+        Ok(())
+    }
+
+    pub fn exec(&mut self, line: &str) -> Result<(), String> {
+        if let Some(cmd) = line.strip_prefix("$ ") {
+            self.listing = false;
+            // Names may contain spaces, so the path is everything after `cd`
+            match cmd.strip_prefix("cd ") {
+                Some(path) if !path.is_empty() => self.cd(path),
+                _ if cmd.trim() == "ls" => {
+                    self.listing = true;
+                    Ok(())
+                }
+                _ => Err(format!("Unknown command: {}", cmd)),
             }
-            cwd.pop();
+        } else if line.is_empty() {
+            Ok(())
+        } else if self.listing {
+            self.list_entry(line)
+        } else {
+            Err(format!("Output without a preceding ls: {}", line))
         }
-    });
-    (root.task_a(), root.task_b())
+    }
+
+    pub fn run(lines: impl Iterator<Item = String>) -> Result<FileSystem, String> {
+        let mut shell = Self::new();
+        for (i, line) in lines.enumerate() {
+            shell
+                .exec(&line)
+                .map_err(|e| format!("Line {}: {}", i + 1, e))?;
+        }
+        Ok(shell.fs)
+    }
+}
+
+/// Creates a new, empty directory in the system's temporary directory.
+fn create_temp_dir() -> io::Result<PathBuf> {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.subsec_nanos());
+    let dir = std::env::temp_dir().join(format!("day7-{}-{}", std::process::id(), nanos));
+    fs::create_dir(&dir)?;
+    Ok(dir)
+}
+
+fn read_fs(path: &str) -> Result<FileSystem, String> {
+    let file = File::open(path).map_err(|e| format!("Couldn't open {}: {}", path, e))?;
+    Shell::run(BufReader::new(file).lines().map(|line| line.unwrap()))
+}
+
+const SMALL_DIR_LIMIT: usize = 100000;
+const DISK_CAPACITY: usize = 70000000;
+const UPDATE_SIZE: usize = 30000000;
+
+fn run_both(path: &str) -> Result<(usize, usize), String> {
+    let fs = read_fs(path)?;
+    Ok((
+        fs.task_a(SMALL_DIR_LIMIT),
+        fs.task_b(DISK_CAPACITY, UPDATE_SIZE)?,
+    ))
 }
 
 fn main() {
-    let (total_score, total_score_b) = run_both("example.txt");
-    println!("The example score is: {} {}", total_score, total_score_b);
+    match run_both("example.txt") {
+        Ok((total_score, total_score_b)) => {
+            println!("The example score is: {} {}", total_score, total_score_b)
+        }
+        Err(e) => println!("Error: {}", e),
+    }
+
+    match run_both("input.txt") {
+        Ok((total_score, total_score_b)) => {
+            println!("The score is: {} {}", total_score, total_score_b)
+        }
+        Err(e) => println!("Error: {}", e),
+    }
+
+    if let Ok(fs) = read_fs("example.txt") {
+        println!("{}", fs.tree(&ReportFilter::default()));
+    }
+    if let Ok(fs) = read_fs("input.txt") {
+        let filter = ReportFilter {
+            max_depth: 1,
+            ..Default::default()
+        };
+        println!("{}", fs.du(&filter));
+        match fs.cleanup_plan(DISK_CAPACITY, UPDATE_SIZE) {
+            Ok(plan) => println!(
+                "Delete {} to free {} bytes",
+                plan.iter()
+                    .map(|id| fs.path(*id))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                plan.iter().map(|id| fs.size(*id)).sum::<usize>()
+            ),
+            Err(e) => println!("Error: {}", e),
+        }
+    }
 
-    let (total_score, total_score_b) = run_both("input.txt");
-    println!("The score is: {} {}", total_score, total_score_b);
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if let Err(e) = run_args(&args) {
+        println!("Error: {}", e);
+    }
+}
+
+/// Handles the optional command line arguments, which export or import the
+/// puzzle input, print a transcript for a real directory, or look up paths.
+fn run_args(args: &[String]) -> Result<(), String> {
+    match args
+        .iter()
+        .map(|a| a.as_str())
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        ["--json"] => println!("{}", read_fs("input.txt")?.to_json()),
+        ["--import", path] => {
+            let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+            let fs = FileSystem::from_json(&Json::parse(&text)?)?;
+            println!("{}", fs.du(&ReportFilter::default()));
+        }
+        ["--materialize"] => {
+            let dir = create_temp_dir().map_err(|e| e.to_string())?;
+            read_fs("input.txt")?
+                .materialize(&dir)
+                .map_err(|e| e.to_string())?;
+            println!("{}", dir.display());
+        }
+        ["--scan", dir] => {
+            let fs = FileSystem::scan(Path::new(dir)).map_err(|e| e.to_string())?;
+            fs.transcript().iter().for_each(|line| println!("{}", line));
+        }
+        paths => {
+            let fs = read_fs("input.txt")?;
+            for path in paths {
+                match fs.lookup(path) {
+                    Some(id) => println!("{}: {}", fs.path(id), fs.size(id)),
+                    None => println!("{}: No such file or directory", path),
+                }
+            }
+        }
+    }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -123,8 +822,177 @@ mod tests {
 
     #[test]
     fn aoc_test() {
-        let (total_score, total_score_b) = run_both("input.txt");
+        let (total_score, total_score_b) = run_both("input.txt").unwrap();
         assert_eq!(total_score, 1792222);
         assert_eq!(total_score_b, 1112963);
     }
+
+    fn run_str(s: &str) -> Result<FileSystem, String> {
+        Shell::run(s.lines().map(|l| l.to_string()))
+    }
+
+    #[test]
+    fn shell_test() {
+        let fs = run_str(
+            "$ cd /\n$ ls\ndir a\n10 b\n$ cd a\n$ ls\ndir c\n5 d\n$ cd c\n$ ls\n7 e\n\
+             $ cd /a/c/../../a\n$ ls\ndir c\n5 d\n$ cd /\n$ ls\ndir a\n10 b",
+        )
+        .unwrap();
+        assert_eq!(fs.size(FileSystem::ROOT), 22);
+        assert_eq!(fs.size(fs.lookup("/a").unwrap()), 12);
+
+        let err = run_str("$ cd /\n$ ls\n10 b\n$ cd a").unwrap_err();
+        assert_eq!(err, "Line 4: No such directory: a");
+        let err = run_str("$ cd /\n$ ls\n10 b\n$ cd b").unwrap_err();
+        assert_eq!(err, "Line 4: Not a directory: b");
+        let err = run_str("$ cd /\n10 b").unwrap_err();
+        assert_eq!(err, "Line 2: Output without a preceding ls: 10 b");
+    }
+
+    #[test]
+    fn cached_size_test() {
+        let mut fs = FileSystem::new();
+        let a = fs.insert_dir(FileSystem::ROOT, "a").unwrap();
+        let b = fs.insert_dir(a, "b").unwrap();
+        fs.insert_file(b, "c", 100).unwrap();
+        fs.insert_file(a, "d", 10).unwrap();
+        assert_eq!(fs.size(FileSystem::ROOT), 110);
+        assert_eq!(fs.size(b), 100);
+
+        fs.insert_file(b, "c", 40).unwrap();
+        assert_eq!(fs.insert_dir(a, "b"), Ok(b));
+        assert_eq!(fs.size(FileSystem::ROOT), 50);
+        assert_eq!(fs.size(a), 50);
+        assert_eq!(
+            fs.lookup("/a/b/c").map(|id| fs.path(id)),
+            Some("/a/b/c".to_string())
+        );
+        assert!(fs.insert_dir(b, "c").is_err());
+    }
+
+    #[test]
+    fn report_test() {
+        let fs = read_fs("example.txt").unwrap();
+        let tree = [
+            "/ (48381165)",
+            "├── a/ (94853)",
+            "│   ├── e/ (584)",
+            "│   │   └── i (584)",
+            "│   ├── f (29116)",
+            "│   ├── g (2557)",
+            "│   └── h.lst (62596)",
+            "├── b.txt (14848514)",
+            "├── c.dat (8504156)",
+            "└── d/ (24933642)",
+            "    ├── d.ext (5626152)",
+            "    ├── d.log (8033020)",
+            "    ├── j (4060174)",
+            "    └── k (7214296)",
+        ];
+        assert_eq!(fs.tree(&ReportFilter::default()), tree.join("\n"));
+        let filter = ReportFilter {
+            min_size: 5000000,
+            max_depth: 1,
+        };
+        assert_eq!(
+            fs.tree(&filter),
+            "/ (48381165)\n├── b.txt (14848514)\n├── c.dat (8504156)\n└── d/ (24933642)"
+        );
+        assert_eq!(
+            fs.du(&ReportFilter::default()),
+            "584\t1\t2\t/a/e\n93K\t4\t1\t/a\n24M\t4\t1\t/d\n47M\t10\t0\t/"
+        );
+        assert_eq!(fs.du(&filter), "24M\t4\t1\t/d\n47M\t10\t0\t/");
+        assert_eq!(human_size(1500), "1.5K");
+        assert_eq!(human_size(1023), "1023");
+        assert_eq!(human_size(10239), "10K");
+        assert_eq!(human_size(1048575), "1.0M");
+        assert_eq!(human_size((1 << 30) - 1), "1.0G");
+    }
+
+    #[test]
+    fn cleanup_test() {
+        let mut fs = FileSystem::new();
+        let a = fs.insert_dir(FileSystem::ROOT, "a").unwrap();
+        let b = fs.insert_dir(a, "b").unwrap();
+        let c = fs.insert_dir(FileSystem::ROOT, "c").unwrap();
+        fs.insert_file(a, "x", 50).unwrap();
+        fs.insert_file(b, "y", 30).unwrap();
+        fs.insert_file(c, "z", 45).unwrap();
+
+        assert_eq!(fs.space_to_free(200, 100), Ok(25));
+        assert_eq!(fs.task_a(50), 75);
+        assert_eq!(fs.cleanup_plan(200, 100), Ok(vec![b]));
+        assert_eq!(fs.cleanup_plan(200, 110), Ok(vec![c]));
+        assert_eq!(fs.cleanup_plan(200, 150), Ok(vec![b, c]));
+        assert_eq!(fs.task_b(200, 150), Ok(80));
+        // Like in the puzzle, `/` counts when no subdirectory is big enough
+        assert_eq!(fs.task_b(200, 200), Ok(125));
+        assert_eq!(fs.cleanup_plan(200, 155), Ok(vec![a]));
+        assert_eq!(fs.cleanup_plan(200, 200), Ok(vec![a, c]));
+        assert_eq!(fs.cleanup_plan(500, 100), Ok(vec![]));
+        assert!(fs.cleanup_plan(100, 50).is_err());
+    }
+
+    #[test]
+    fn cleanup_blocks_test() {
+        let mut fs = FileSystem::new();
+        let a = fs.insert_dir(FileSystem::ROOT, "a").unwrap();
+        let b = fs.insert_dir(FileSystem::ROOT, "b").unwrap();
+        let c = fs.insert_dir(FileSystem::ROOT, "c").unwrap();
+        fs.insert_file(a, "x", 3 << 38).unwrap();
+        fs.insert_file(b, "y", 1 << 39).unwrap();
+        fs.insert_file(c, "z", (1 << 39) + 1).unwrap();
+        let used = fs.size(FileSystem::ROOT);
+
+        // Far too big for a table with a bit per byte
+        assert_eq!(fs.cleanup_plan(used, 1 << 40), Ok(vec![b, c]));
+        let freed = |plan: Vec<NodeId>| plan.iter().map(|id| fs.size(*id)).sum::<usize>();
+        for goal in [(1 << 40) + 1, (1 << 40) + 2, 7 << 37] {
+            assert!(freed(fs.cleanup_plan(used, goal).unwrap()) >= goal);
+        }
+    }
+
+    #[test]
+    fn export_test() {
+        let fs = read_fs("example.txt").unwrap();
+        let json = fs.to_json().to_string();
+        assert!(json.starts_with(r#"{"name":"","type":"dir","children":[{"name":"a","type":"dir""#));
+        let imported = FileSystem::from_json(&Json::parse(&json).unwrap()).unwrap();
+        assert_eq!(imported.to_json().to_string(), json);
+        assert!(
+            FileSystem::from_json(&Json::parse(r#"{"children":[{"name":"x"}]}"#).unwrap()).is_err()
+        );
+        for name in ["..", "a/b", "/etc/x", ""] {
+            let json = format!(
+                r#"{{"children":[{{"name":"{}","type":"dir","children":[]}}]}}"#,
+                name
+            );
+            assert!(FileSystem::from_json(&Json::parse(&json).unwrap()).is_err());
+        }
+
+        let replayed = Shell::run(fs.transcript().into_iter()).unwrap();
+        assert_eq!(replayed.to_json(), fs.to_json());
+
+        let dir = create_temp_dir().unwrap();
+        fs.materialize(&dir).unwrap();
+        let scanned = FileSystem::scan(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(scanned.unwrap().to_json(), fs.to_json());
+
+        let mut fs = FileSystem::new();
+        let docs = fs.insert_dir(FileSystem::ROOT, "My Documents").unwrap();
+        let old = fs.insert_dir(docs, "old files").unwrap();
+        fs.insert_file(old, "notes 2.txt", 42).unwrap();
+        let dir = create_temp_dir().unwrap();
+        fs.materialize(&dir).unwrap();
+        let scanned = FileSystem::scan(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+        let scanned = scanned.unwrap();
+        assert!(scanned
+            .transcript()
+            .contains(&"$ cd My Documents".to_string()));
+        let replayed = Shell::run(scanned.transcript().into_iter()).unwrap();
+        assert_eq!(replayed.to_json(), fs.to_json());
+    }
 }
@@ -1,103 +1,293 @@
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 
-fn is_visible_rec(
-    arr: &Vec<Vec<u32>>,
-    pos: [i32; 2],
-    size: [i32; 2],
-    vel: [i32; 2],
-    goal: u32,
-) -> bool {
-    if pos[0] < 0 || pos[1] < 0 || pos[0] >= size[0] || pos[1] >= size[1] {
-        return true;
-    }
-    let v = arr[pos[0] as usize][pos[1] as usize];
-    if v >= goal {
-        return false;
-    }
-    let pos = [pos[0] + vel[0], pos[1] + vel[1]];
-    return is_visible_rec(arr, pos, size, vel, goal);
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
 }
 
-fn is_visible(arr: &Vec<Vec<u32>>, pos: [i32; 2], size: [i32; 2]) -> bool {
-    if pos[0] == 0 || pos[1] == 0 || pos[0] == size[0] - 1 || pos[1] == size[1] - 1 {
-        return true;
-    }
-    let goal = arr[pos[0] as usize][pos[1] as usize];
-    is_visible_rec(arr, [pos[0] + 1, pos[1]], size, [1, 0], goal)
-        || is_visible_rec(arr, [pos[0], pos[1] + 1], size, [0, 1], goal)
-        || is_visible_rec(arr, [pos[0] - 1, pos[1]], size, [-1, 0], goal)
-        || is_visible_rec(arr, [pos[0], pos[1] - 1], size, [0, -1], goal)
+impl Direction {
+    pub const ALL: [Direction; 4] = [Self::Up, Self::Down, Self::Left, Self::Right];
 }
 
-fn count_trees_rec(
-    arr: &Vec<Vec<u32>>,
-    pos: [i32; 2],
-    size: [i32; 2],
-    vel: [i32; 2],
-    goal: u32,
-    acc: i32,
-) -> i32 {
-    if pos[0] < 0 || pos[1] < 0 || pos[0] >= size[0] || pos[1] >= size[1] {
-        return acc;
-    }
-    let v = arr[pos[0] as usize][pos[1] as usize];
-    if v >= goal {
-        return acc + 1;
-    }
-    let pos = [pos[0] + vel[0], pos[1] + vel[1]];
-    return count_trees_rec(arr, pos, size, vel, goal, acc + 1);
+/// What a single tree sees when looking in each [`Direction`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+struct Sight {
+    /// Whether the tree can be seen from outside the grid in that direction.
+    visible: [bool; 4],
+    /// Number of trees visible from the tree before the view is blocked.
+    distance: [usize; 4],
 }
 
-fn count_trees(arr: &Vec<Vec<u32>>, pos: [i32; 2], size: [i32; 2]) -> i32 {
-    let goal = arr[pos[0] as usize][pos[1] as usize];
-    count_trees_rec(arr, [pos[0] + 1, pos[1]], size, [1, 0], goal, 0)
-        * count_trees_rec(arr, [pos[0], pos[1] + 1], size, [0, 1], goal, 0)
-        * count_trees_rec(arr, [pos[0] - 1, pos[1]], size, [-1, 0], goal, 0)
-        * count_trees_rec(arr, [pos[0], pos[1] - 1], size, [0, -1], goal, 0)
+impl Sight {
+    pub fn is_visible(&self) -> bool {
+        self.visible.iter().any(|v| *v)
+    }
+
+    pub fn scenic_score(&self) -> usize {
+        self.distance.iter().product()
+    }
+
+    pub fn visible_from(&self, dir: Direction) -> bool {
+        self.visible[dir as usize]
+    }
+
+    pub fn distance(&self, dir: Direction) -> usize {
+        self.distance[dir as usize]
+    }
 }
 
-fn run_both(path: &str) -> (usize, usize) {
-    let file = File::open(path).unwrap();
-    let arr = BufReader::new(file)
-        .lines()
-        .map(|l| {
-            l.unwrap()
-                .chars()
-                .map(|c| c.to_digit(10).unwrap())
-                .collect::<Vec<_>>()
-        })
-        .collect::<Vec<_>>();
-    let height = arr.len() as i32;
-    let width = arr[0].len() as i32;
-    let a = arr.iter().enumerate().fold(0, |acc, (y, cur)| {
-        acc + cur.iter().enumerate().fold(0, |acc, (x, _cur)| {
-            acc + is_visible(&arr, [x as i32, y as i32], [width, height]) as usize
+/// Walks along a line of trees keeping a stack of the trees that could still
+/// block the view, with heights decreasing towards the top. Every tree gets
+/// pushed and popped once, so the whole line takes linear time. Returns for
+/// every tree whether it's visible from the start of the line and how far
+/// it can see towards the start.
+fn sweep_line(heights: &[u32]) -> Vec<(bool, usize)> {
+    let mut stack: Vec<usize> = Vec::with_capacity(heights.len());
+    heights
+        .iter()
+        .enumerate()
+        .map(|(i, h)| {
+            while stack.last().is_some_and(|j| heights[*j] < *h) {
+                stack.pop();
+            }
+            let ret = match stack.last() {
+                Some(j) => (false, i - j),
+                None => (true, i),
+            };
+            stack.push(i);
+            ret
         })
-    });
+        .collect()
+}
 
-    let b = arr
-        .iter()
+/// Sweeps every row and column from both ends, giving the [`Sight`] of every
+/// tree in O(width * height).
+fn analyze(arr: &[Vec<u32>]) -> Vec<Vec<Sight>> {
+    let height = arr.len();
+    let width = arr.first().map_or(0, |row| row.len());
+    let mut ret = vec![vec![Sight::default(); width]; height];
+    let mut record = |dir: Direction, cells: &[(usize, usize)]| {
+        let line = cells.iter().map(|(y, x)| arr[*y][*x]).collect::<Vec<_>>();
+        for ((y, x), (visible, distance)) in cells.iter().zip(sweep_line(&line)) {
+            ret[*y][*x].visible[dir as usize] = visible;
+            ret[*y][*x].distance[dir as usize] = distance;
+        }
+    };
+    for y in 0..height {
+        let mut cells = (0..width).map(|x| (y, x)).collect::<Vec<_>>();
+        record(Direction::Left, &cells);
+        cells.reverse();
+        record(Direction::Right, &cells);
+    }
+    for x in 0..width {
+        let mut cells = (0..height).map(|y| (y, x)).collect::<Vec<_>>();
+        record(Direction::Up, &cells);
+        cells.reverse();
+        record(Direction::Down, &cells);
+    }
+    ret
+}
+
+/// The original ray casting implementation, kept to cross-check the sweeps.
+#[cfg(test)]
+mod ray_cast {
+    fn is_visible_rec(
+        arr: &Vec<Vec<u32>>,
+        pos: [i32; 2],
+        size: [i32; 2],
+        vel: [i32; 2],
+        goal: u32,
+    ) -> bool {
+        if pos[0] < 0 || pos[1] < 0 || pos[0] >= size[0] || pos[1] >= size[1] {
+            return true;
+        }
+        let v = arr[pos[0] as usize][pos[1] as usize];
+        if v >= goal {
+            return false;
+        }
+        let pos = [pos[0] + vel[0], pos[1] + vel[1]];
+        is_visible_rec(arr, pos, size, vel, goal)
+    }
+
+    pub fn is_visible(arr: &Vec<Vec<u32>>, pos: [i32; 2], size: [i32; 2]) -> bool {
+        if pos[0] == 0 || pos[1] == 0 || pos[0] == size[0] - 1 || pos[1] == size[1] - 1 {
+            return true;
+        }
+        let goal = arr[pos[0] as usize][pos[1] as usize];
+        is_visible_rec(arr, [pos[0] + 1, pos[1]], size, [1, 0], goal)
+            || is_visible_rec(arr, [pos[0], pos[1] + 1], size, [0, 1], goal)
+            || is_visible_rec(arr, [pos[0] - 1, pos[1]], size, [-1, 0], goal)
+            || is_visible_rec(arr, [pos[0], pos[1] - 1], size, [0, -1], goal)
+    }
+
+    fn count_trees_rec(
+        arr: &Vec<Vec<u32>>,
+        pos: [i32; 2],
+        size: [i32; 2],
+        vel: [i32; 2],
+        goal: u32,
+        acc: i32,
+    ) -> i32 {
+        if pos[0] < 0 || pos[1] < 0 || pos[0] >= size[0] || pos[1] >= size[1] {
+            return acc;
+        }
+        let v = arr[pos[0] as usize][pos[1] as usize];
+        if v >= goal {
+            return acc + 1;
+        }
+        let pos = [pos[0] + vel[0], pos[1] + vel[1]];
+        count_trees_rec(arr, pos, size, vel, goal, acc + 1)
+    }
+
+    pub fn count_trees(arr: &Vec<Vec<u32>>, pos: [i32; 2], size: [i32; 2]) -> i32 {
+        let goal = arr[pos[0] as usize][pos[1] as usize];
+        count_trees_rec(arr, [pos[0] + 1, pos[1]], size, [1, 0], goal, 0)
+            * count_trees_rec(arr, [pos[0], pos[1] + 1], size, [0, 1], goal, 0)
+            * count_trees_rec(arr, [pos[0] - 1, pos[1]], size, [-1, 0], goal, 0)
+            * count_trees_rec(arr, [pos[0], pos[1] - 1], size, [0, -1], goal, 0)
+    }
+}
+
+/// Parses a height map, either as one digit per tree or, when a line
+/// contains separators, as whitespace or comma separated numbers.
+fn parse_grid(lines: impl Iterator<Item = String>) -> Result<Vec<Vec<u32>>, String> {
+    let arr = lines
         .enumerate()
-        .map(|(y, cur)| {
-            cur.iter()
-                .enumerate()
-                .map(|(x, _cur)| count_trees(&arr, [x as i32, y as i32], [width, height]) as usize)
-                .max()
-                .unwrap()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            let row = if line.contains(|c: char| c.is_whitespace() || c == ',') {
+                line.split(|c: char| c.is_whitespace() || c == ',')
+                    .filter(|v| !v.is_empty())
+                    .map(|v| v.parse::<u32>().ok())
+                    .collect::<Option<Vec<_>>>()
+            } else {
+                line.chars().map(|c| c.to_digit(10)).collect()
+            };
+            row.ok_or(format!("Line {}: Invalid height in: {}", i + 1, line))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if let Some(row) = arr.iter().find(|row| row.len() != arr[0].len()) {
+        return Err(format!(
+            "All rows need {} trees, found a row with {}",
+            arr[0].len(),
+            row.len()
+        ));
+    }
+    Ok(arr)
+}
+
+/// A height map together with the [`Sight`] of every tree on it.
+#[derive(Clone, Debug)]
+struct Forest {
+    heights: Vec<Vec<u32>>,
+    sights: Vec<Vec<Sight>>,
+}
+
+impl Forest {
+    pub fn new(heights: Vec<Vec<u32>>) -> Self {
+        let sights = analyze(&heights);
+        Self { heights, sights }
+    }
+
+    pub fn read(path: &str) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| format!("Couldn't open {}: {}", path, e))?;
+        let lines = BufReader::new(file).lines().map(|l| l.unwrap());
+        Ok(Self::new(parse_grid(lines)?))
+    }
+
+    pub fn width(&self) -> usize {
+        self.heights.first().map_or(0, |row| row.len())
+    }
+
+    pub fn height(&self) -> usize {
+        self.heights.len()
+    }
+
+    pub fn sight(&self, x: usize, y: usize) -> Option<&Sight> {
+        self.sights.get(y)?.get(x)
+    }
+
+    pub fn visible_count(&self) -> usize {
+        self.sights
+            .iter()
+            .flatten()
+            .filter(|s| s.is_visible())
+            .count()
+    }
+
+    /// Number of trees that can be seen from outside the grid when looking
+    /// towards them from each direction.
+    pub fn visible_counts(&self) -> [usize; 4] {
+        Direction::ALL.map(|dir| {
+            self.sights
+                .iter()
+                .flatten()
+                .filter(|s| s.visible_from(dir))
+                .count()
         })
-        .max()
-        .unwrap();
+    }
 
-    (a, b)
+    /// The highest scenic score and every `[x, y]` position reaching it.
+    pub fn best_spots(&self) -> (usize, Vec<[usize; 2]>) {
+        let best = self
+            .sights
+            .iter()
+            .flatten()
+            .map(|s| s.scenic_score())
+            .max()
+            .unwrap_or(0);
+        let spots = (0..self.height())
+            .flat_map(|y| (0..self.width()).map(move |x| [x, y]))
+            .filter(|[x, y]| self.sights[*y][*x].scenic_score() == best)
+            .collect();
+        (best, spots)
+    }
+}
+
+fn run_both(path: &str) -> Result<(usize, usize), String> {
+    let forest = Forest::read(path)?;
+    Ok((forest.visible_count(), forest.best_spots().0))
 }
 
 fn main() {
-    let (total_score, total_score_b) = run_both("example.txt");
-    println!("The example score is: {} {}", total_score, total_score_b);
+    match run_both("example.txt") {
+        Ok((total_score, total_score_b)) => {
+            println!("The example score is: {} {}", total_score, total_score_b)
+        }
+        Err(e) => println!("Error: {}", e),
+    }
 
-    let (total_score, total_score_b) = run_both("input.txt");
-    println!("The score is: {} {}", total_score, total_score_b);
+    match run_both("input.txt") {
+        Ok((total_score, total_score_b)) => {
+            println!("The score is: {} {}", total_score, total_score_b)
+        }
+        Err(e) => println!("Error: {}", e),
+    }
+
+    if let Ok(forest) = Forest::read("input.txt") {
+        let (score, spots) = forest.best_spots();
+        for [x, y] in spots {
+            let sight = forest.sight(x, y).unwrap();
+            println!(
+                "Best spot at {},{} with a score of {}, viewing distances: {:?}",
+                x,
+                y,
+                score,
+                Direction::ALL.map(|dir| (dir, sight.distance(dir)))
+            );
+        }
+        println!(
+            "Trees visible by direction: {:?}",
+            Direction::ALL
+                .iter()
+                .zip(forest.visible_counts())
+                .collect::<Vec<_>>()
+        );
+    }
 }
 
 #[cfg(test)]
@@ -106,8 +296,41 @@ mod tests {
 
     #[test]
     fn aoc_test() {
-        let (total_score, total_score_b) = run_both("input.txt");
+        let (total_score, total_score_b) = run_both("input.txt").unwrap();
         assert_eq!(total_score, 1801);
         assert_eq!(total_score_b, 209880);
     }
+
+    #[test]
+    fn ray_cast_test() {
+        let arr = Forest::read("input.txt").unwrap().heights;
+        let size = [arr.len() as i32, arr[0].len() as i32];
+        for (y, row) in analyze(&arr).iter().enumerate() {
+            for (x, sight) in row.iter().enumerate() {
+                let pos = [y as i32, x as i32];
+                assert_eq!(sight.is_visible(), ray_cast::is_visible(&arr, pos, size));
+                assert_eq!(
+                    sight.scenic_score(),
+                    ray_cast::count_trees(&arr, pos, size) as usize
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn forest_test() {
+        let forest = Forest::read("example.txt").unwrap();
+        assert_eq!(forest.best_spots(), (8, vec![[2, 3]]));
+        let sight = forest.sight(2, 3).unwrap();
+        assert_eq!(Direction::ALL.map(|dir| sight.distance(dir)), [2, 1, 2, 2]);
+        assert_eq!(forest.visible_counts(), [10, 8, 11, 11]);
+
+        let lines = ["10 2 30 4", "5, 60, 7, 8", "", "9 10 11 12"];
+        let forest = Forest::new(parse_grid(lines.iter().map(|l| l.to_string())).unwrap());
+        assert_eq!([forest.width(), forest.height()], [4, 3]);
+        assert_eq!(forest.visible_count(), 11);
+        assert_eq!(forest.best_spots(), (2, vec![[1, 1]]));
+        assert!(parse_grid(["12", "123"].iter().map(|l| l.to_string())).is_err());
+        assert!(parse_grid(["1a"].iter().map(|l| l.to_string())).is_err());
+    }
 }
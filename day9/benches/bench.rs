@@ -0,0 +1,41 @@
+use std::collections::HashSet;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use day9::{read_moves, visited_counts, Move, Rope};
+
+/// Counts the positions every knot visits with one `HashSet` per knot,
+/// which is what `visited_counts` does with bitmaps.
+fn hashset_counts(moves: &[Move<2>], knots: usize, slack: i32) -> Vec<usize> {
+    let mut rope = Rope::<2>::new(knots, slack);
+    let mut visited = vec![HashSet::new(); knots];
+    for (set, knot) in visited.iter_mut().zip(rope.knots.iter()) {
+        set.insert(*knot);
+    }
+    for m in moves {
+        for _ in 0..m.steps {
+            rope.step(m.dir);
+            for (set, knot) in visited.iter_mut().zip(rope.knots.iter()) {
+                set.insert(*knot);
+            }
+        }
+    }
+    visited.iter().map(HashSet::len).collect()
+}
+
+pub fn criterion_benchmark(c: &mut Criterion) {
+    let moves = read_moves::<2>("input.txt").unwrap();
+    assert_eq!(hashset_counts(&moves, 10, 1), visited_counts(&moves, 10, 1));
+    c.bench_function("Day9 HashSet", |b| {
+        b.iter(|| hashset_counts(black_box(&moves), 10, 1))
+    });
+    c.bench_function("Day9 bitmap", |b| {
+        b.iter(|| visited_counts(black_box(&moves), 10, 1))
+    });
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(20);
+    targets = criterion_benchmark
+}
+criterion_main!(benches);
@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+
+/// Bitmaps that together take up more than this many bits, which is
+/// 16 MB, are stored in chunks instead.
+const DENSE_LIMIT: usize = 1 << 27;
+const CHUNK_BITS: i32 = 6;
+const CHUNK_SIZE: i32 = 1 << CHUNK_BITS;
+
+/// A set of cells within a fixed rectangle, using a single bit per cell.
+#[derive(Clone, Debug)]
+pub struct DenseBitmap {
+    min: [i32; 2],
+    width: usize,
+    height: usize,
+    bits: Vec<u64>,
+    len: usize,
+}
+
+impl DenseBitmap {
+    /// Creates an empty bitmap covering `min..=max` along both axes.
+    pub fn new(min: [i32; 2], max: [i32; 2]) -> Self {
+        let width = (max[0] - min[0] + 1) as usize;
+        let height = (max[1] - min[1] + 1) as usize;
+        Self {
+            min,
+            width,
+            height,
+            bits: vec![0; (width * height).div_ceil(64)],
+            len: 0,
+        }
+    }
+
+    fn index(&self, pos: [i32; 2]) -> Option<usize> {
+        let x = usize::try_from(pos[0] - self.min[0]).ok()?;
+        let y = usize::try_from(pos[1] - self.min[1]).ok()?;
+        if x < self.width && y < self.height {
+            Some(y * self.width + x)
+        } else {
+            None
+        }
+    }
+
+    /// Adds a cell, returning whether it wasn't set before.
+    ///
+    /// Panics when the cell lies outside of the bitmap's bounds.
+    pub fn insert(&mut self, pos: [i32; 2]) -> bool {
+        let i = self
+            .index(pos)
+            .unwrap_or_else(|| panic!("Position {:?} is out of bounds", pos));
+        let mask = 1 << (i % 64);
+        let new = self.bits[i / 64] & mask == 0;
+        self.bits[i / 64] |= mask;
+        self.len += new as usize;
+        new
+    }
+
+    pub fn contains(&self, pos: [i32; 2]) -> bool {
+        self.index(pos)
+            .is_some_and(|i| self.bits[i / 64] & (1 << (i % 64)) != 0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// A set of cells without any bounds, split into 64x64 chunks that only get
+/// allocated once a cell within them is set.
+#[derive(Clone, Debug, Default)]
+pub struct ChunkedBitmap {
+    chunks: HashMap<[i32; 2], Box<[u64; CHUNK_SIZE as usize]>>,
+    len: usize,
+}
+
+impl ChunkedBitmap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn split(pos: [i32; 2]) -> ([i32; 2], usize, u64) {
+        let key = [pos[0] >> CHUNK_BITS, pos[1] >> CHUNK_BITS];
+        let row = (pos[1] & (CHUNK_SIZE - 1)) as usize;
+        let mask = 1 << (pos[0] & (CHUNK_SIZE - 1));
+        (key, row, mask)
+    }
+
+    /// Adds a cell, returning whether it wasn't set before.
+    pub fn insert(&mut self, pos: [i32; 2]) -> bool {
+        let (key, row, mask) = Self::split(pos);
+        let chunk = self
+            .chunks
+            .entry(key)
+            .or_insert_with(|| Box::new([0; CHUNK_SIZE as usize]));
+        let new = chunk[row] & mask == 0;
+        chunk[row] |= mask;
+        self.len += new as usize;
+        new
+    }
+
+    pub fn contains(&self, pos: [i32; 2]) -> bool {
+        let (key, row, mask) = Self::split(pos);
+        self.chunks
+            .get(&key)
+            .is_some_and(|chunk| chunk[row] & mask != 0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// A set of cells that picks its storage based on the area it has to cover.
+#[derive(Clone, Debug)]
+pub enum Bitmap {
+    Dense(DenseBitmap),
+    Chunked(ChunkedBitmap),
+}
+
+impl Bitmap {
+    /// Creates an empty bitmap for cells within `min..=max`, which only
+    /// gets stored densely if that doesn't take up too much memory.
+    pub fn for_bounds(min: [i32; 2], max: [i32; 2]) -> Self {
+        Self::many_for_bounds(min, max, 1).pop().unwrap()
+    }
+
+    /// Creates `count` empty bitmaps like `for_bounds`, which all share the
+    /// memory budget for dense storage.
+    pub fn many_for_bounds(min: [i32; 2], max: [i32; 2], count: usize) -> Vec<Self> {
+        let area = (max[0] - min[0] + 1) as u64 * (max[1] - min[1] + 1) as u64;
+        let bitmap = if area.saturating_mul(count as u64) <= DENSE_LIMIT as u64 {
+            Self::Dense(DenseBitmap::new(min, max))
+        } else {
+            Self::Chunked(ChunkedBitmap::new())
+        };
+        vec![bitmap; count]
+    }
+
+    pub fn insert(&mut self, pos: [i32; 2]) -> bool {
+        match self {
+            Self::Dense(b) => b.insert(pos),
+            Self::Chunked(b) => b.insert(pos),
+        }
+    }
+
+    pub fn contains(&self, pos: [i32; 2]) -> bool {
+        match self {
+            Self::Dense(b) => b.contains(pos),
+            Self::Chunked(b) => b.contains(pos),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Dense(b) => b.len(),
+            Self::Chunked(b) => b.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
@@ -0,0 +1,237 @@
+pub mod bitmap;
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+use bitmap::Bitmap;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RopeError {
+    Io(String),
+    UnknownDirection { line: usize, direction: String },
+    InvalidSteps { line: usize, steps: String },
+}
+
+impl fmt::Display for RopeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{}", e),
+            Self::UnknownDirection { line, direction } => {
+                write!(f, "Line {}: Unknown direction: {}", line, direction)
+            }
+            Self::InvalidSteps { line, steps } => {
+                write!(f, "Line {}: Invalid step count: {}", line, steps)
+            }
+        }
+    }
+}
+
+/// Parses a direction made up of one letter per axis, like `U` or `DL`.
+/// `L`/`R` move along the first axis, `D`/`U` along the second and
+/// `B`/`F` along the third one.
+pub fn parse_direction<const N: usize>(s: &str, line: usize) -> Result<[i32; N], RopeError> {
+    let err = || RopeError::UnknownDirection {
+        line,
+        direction: s.to_string(),
+    };
+    let mut dir = [0; N];
+    for c in s.chars() {
+        let (axis, v) = match c {
+            'L' => (0, -1),
+            'R' => (0, 1),
+            'D' => (1, -1),
+            'U' => (1, 1),
+            'B' => (2, -1),
+            'F' => (2, 1),
+            _ => return Err(err()),
+        };
+        if axis >= N || dir[axis] != 0 {
+            return Err(err());
+        }
+        dir[axis] = v;
+    }
+    if s.is_empty() {
+        return Err(err());
+    }
+    Ok(dir)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Move<const N: usize> {
+    pub dir: [i32; N],
+    pub steps: u32,
+}
+
+pub fn parse_moves<const N: usize>(
+    lines: impl Iterator<Item = String>,
+) -> Result<Vec<Move<N>>, RopeError> {
+    lines
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            let mut parts = line.split_whitespace();
+            let dir = parse_direction(parts.next().unwrap_or(""), i + 1)?;
+            let steps = parts.next().unwrap_or("");
+            let steps = steps.parse::<u32>().map_err(|_| RopeError::InvalidSteps {
+                line: i + 1,
+                steps: steps.to_string(),
+            })?;
+            Ok(Move { dir, steps })
+        })
+        .collect()
+}
+
+/// Moves `tail` one step towards `head` once it's further than `slack`
+/// away along any axis.
+pub fn tail_step<const N: usize>(tail: [i32; N], head: [i32; N], slack: i32) -> [i32; N] {
+    if (0..N).any(|i| (head[i] - tail[i]).abs() > slack) {
+        let mut ret = tail;
+        for i in 0..N {
+            ret[i] += (head[i] - tail[i]).clamp(-1, 1);
+        }
+        ret
+    } else {
+        tail
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Rope<const N: usize> {
+    pub knots: Vec<[i32; N]>,
+    pub slack: i32,
+}
+
+impl<const N: usize> Rope<N> {
+    pub fn new(knots: usize, slack: i32) -> Self {
+        assert!(knots > 0, "A rope needs at least one knot");
+        Self {
+            knots: vec![[0; N]; knots],
+            slack,
+        }
+    }
+
+    /// Moves the head one step in `dir` and lets every other knot follow.
+    pub fn step(&mut self, dir: [i32; N]) {
+        self.knots[0].iter_mut().zip(dir).for_each(|(p, d)| *p += d);
+        for i in 1..self.knots.len() {
+            self.knots[i] = tail_step(self.knots[i], self.knots[i - 1], self.slack);
+        }
+    }
+}
+
+pub fn read_moves<const N: usize>(path: &str) -> Result<Vec<Move<N>>, RopeError> {
+    let file = File::open(path).map_err(|e| RopeError::Io(format!("{}: {}", path, e)))?;
+    parse_moves(BufReader::new(file).lines().map(|line| line.unwrap()))
+}
+
+/// Everything recorded about a single knot during a simulation.
+#[derive(Clone, Debug)]
+pub struct KnotTrack<const N: usize> {
+    /// Position before the first step and after every step.
+    pub trajectory: Vec<[i32; N]>,
+    pub visited: HashSet<[i32; N]>,
+    /// Number of the step during which the knot moved for the first time.
+    pub first_move: Option<usize>,
+    min: [i32; N],
+    max: [i32; N],
+}
+
+impl<const N: usize> KnotTrack<N> {
+    pub fn new(start: [i32; N]) -> Self {
+        Self {
+            trajectory: vec![start],
+            visited: [start].into(),
+            first_move: None,
+            min: start,
+            max: start,
+        }
+    }
+
+    fn record(&mut self, step: usize, pos: [i32; N]) {
+        if self.first_move.is_none() && self.trajectory.last() != Some(&pos) {
+            self.first_move = Some(step);
+        }
+        self.trajectory.push(pos);
+        self.visited.insert(pos);
+        for (i, p) in pos.iter().enumerate() {
+            self.min[i] = self.min[i].min(*p);
+            self.max[i] = self.max[i].max(*p);
+        }
+    }
+
+    pub fn distinct_positions(&self) -> usize {
+        self.visited.len()
+    }
+
+    /// Smallest and largest coordinate the knot reached along every axis.
+    pub fn bounding_box(&self) -> ([i32; N], [i32; N]) {
+        (self.min, self.max)
+    }
+}
+
+/// Simulates a rope and tracks every one of its knots along the way.
+pub fn track<const N: usize>(moves: &[Move<N>], knots: usize, slack: i32) -> Vec<KnotTrack<N>> {
+    let mut rope = Rope::<N>::new(knots, slack);
+    let mut tracks = rope
+        .knots
+        .iter()
+        .map(|k| KnotTrack::new(*k))
+        .collect::<Vec<_>>();
+    let mut step = 0;
+    for m in moves {
+        for _ in 0..m.steps {
+            step += 1;
+            rope.step(m.dir);
+            for (track, knot) in tracks.iter_mut().zip(rope.knots.iter()) {
+                track.record(step, *knot);
+            }
+        }
+    }
+    tracks
+}
+
+/// Smallest and largest position the head reaches along every axis,
+/// including its starting point at the origin.
+pub fn head_bounds<const N: usize>(moves: &[Move<N>]) -> ([i32; N], [i32; N]) {
+    let (mut min, mut max, mut pos) = ([0; N], [0; N], [0; N]);
+    for m in moves {
+        for i in 0..N {
+            pos[i] += m.dir[i] * m.steps as i32;
+            min[i] = min[i].min(pos[i]);
+            max[i] = max[i].max(pos[i]);
+        }
+    }
+    (min, max)
+}
+
+/// Counts the distinct positions of every knot of a flat rope without
+/// keeping any trajectories around.
+///
+/// A knot never moves past the knot in front of it, so every knot stays
+/// within the head's bounding box, which is what the bitmaps are sized by.
+pub fn visited_counts(moves: &[Move<2>], knots: usize, slack: i32) -> Vec<usize> {
+    let (min, max) = head_bounds(moves);
+    let mut rope = Rope::<2>::new(knots, slack);
+    let mut visited = Bitmap::many_for_bounds(min, max, knots);
+    for (bitmap, knot) in visited.iter_mut().zip(rope.knots.iter()) {
+        bitmap.insert(*knot);
+    }
+    for m in moves {
+        for _ in 0..m.steps {
+            rope.step(m.dir);
+            for (bitmap, knot) in visited.iter_mut().zip(rope.knots.iter()) {
+                bitmap.insert(*knot);
+            }
+        }
+    }
+    visited.iter().map(Bitmap::len).collect()
+}
+
+/// The knots of a shorter rope move exactly like the first knots of a
+/// longer one, so a single ten knot simulation answers both parts.
+pub fn run_both(path: &str) -> Result<(usize, usize), RopeError> {
+    let counts = visited_counts(&read_moves::<2>(path)?, 10, 1);
+    Ok((counts[1], counts[9]))
+}
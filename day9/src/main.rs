@@ -1,70 +1,140 @@
-use std::collections::HashSet;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+use day9::{read_moves, run_both, track};
 
-fn head_step(head:[i32; 2], direction: char) -> [i32; 2] {
-    match direction {
-        'U' => [head[0], head[1] + 1],
-        'D' => [head[0], head[1] - 1],
-        'L' => [head[0] - 1, head[1]],
-        'R' => [head[0] + 1, head[1]],
-        _ => panic!("Unknown direction: {}", direction)
+fn main() {
+    match run_both("example.txt") {
+        Ok((total_score, total_score_b)) => {
+            println!("The example score is: {} {}", total_score, total_score_b)
+        }
+        Err(e) => println!("Error: {}", e),
     }
-}
 
-fn tail_step(tail: [i32; 2], head:[i32; 2]) -> [i32; 2] {
-    let dx = head[0] - tail[0];
-    let dy = head[1] - tail[1];
-    if dx.abs() > 1 || dy.abs() > 1 {
-        [tail[0] + dx.clamp(-1,1), tail[1] + dy.clamp(-1,1)]
-    } else {
-        tail
+    match run_both("input.txt") {
+        Ok((total_score, total_score_b)) => {
+            println!("The score is: {} {}", total_score, total_score_b)
+        }
+        Err(e) => println!("Error: {}", e),
     }
-}
-
-fn run(path: &str, knots: usize) -> usize {
-    let file = File::open(path).unwrap();
-    let mut rope = std::iter::repeat([0, 0]).take(knots).collect::<Vec<_>>();
-    let mut visited_positions:HashSet<[i32; 2]> = [[0, 0]].into();
 
-    BufReader::new(file)
-        .lines()
-        .for_each(|line| {
-            let line = line.unwrap();
-            let mut chars = line.chars();
-            let direction = chars.next().unwrap();
-            let steps = chars.collect::<String>().trim().parse::<u32>().unwrap();
-            for _ in 0..steps {
-                rope[0] = head_step(rope[0], direction);
-                for i in 1..rope.len() {\
-                    rope[i] = tail_step(rope[i], rope[i-1]);
-                }
-                visited_positions.insert(rope[rope.len()-1]);
-            }
-        });
-    visited_positions.len()
-}
-
-fn run_both(path: &str) -> (usize, usize) {
-    (run(path, 2), run(path, 10))
-}
-
-fn main() {
-    let (total_score, total_score_b) = run_both("example.txt");
-    println!("The example score is: {} {}", total_score, total_score_b);
-
-    let (total_score, total_score_b) = run_both("input.txt");
-    println!("The score is: {} {}", total_score, total_score_b);
+    if let Ok(moves) = read_moves::<2>("input.txt") {
+        for (i, knot) in track(&moves, 10, 1).iter().enumerate() {
+            let (min, max) = knot.bounding_box();
+            println!(
+                "Knot {}: {} positions, first moved in step {:?}, bounding box {:?} to {:?}",
+                i,
+                knot.distinct_positions(),
+                knot.first_move,
+                min,
+                max
+            );
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use day9::bitmap::{Bitmap, ChunkedBitmap, DenseBitmap};
+    use day9::{parse_moves, visited_counts, Move, Rope, RopeError};
+
+    fn moves<const N: usize>(s: &str) -> Result<Vec<Move<N>>, RopeError> {
+        parse_moves(s.lines().map(|l| l.to_string()))
+    }
 
     #[test]
     fn aoc_test() {
-        let (total_score, total_score_b) = run_both("input.txt");
+        let (total_score, total_score_b) = run_both("input.txt").unwrap();
         assert_eq!(total_score, 6470);
         assert_eq!(total_score_b, 2658);
     }
+
+    #[test]
+    fn rope_test() {
+        assert_eq!(run_both("example.txt").unwrap(), (13, 1));
+
+        let diagonal = moves::<2>("UR 3\nDL 1").unwrap();
+        assert_eq!(diagonal[0].dir, [1, 1]);
+        assert_eq!(track(&diagonal, 2, 1)[1].distinct_positions(), 3);
+        assert_eq!(track(&diagonal, 2, 2)[1].distinct_positions(), 2);
+
+        let spatial = moves::<3>("F 3\nUF 2").unwrap();
+        let mut rope = Rope::<3>::new(3, 1);
+        for m in spatial.iter() {
+            (0..m.steps).for_each(|_| rope.step(m.dir));
+        }
+        assert_eq!(rope.knots, [[0, 2, 5], [0, 2, 4], [0, 2, 3]]);
+
+        assert_eq!(
+            moves::<2>("R 1\nF 2"),
+            Err(RopeError::UnknownDirection {
+                line: 2,
+                direction: "F".to_string()
+            })
+        );
+        assert!(moves::<2>("UD 1").is_err());
+
+        let tracks = track(&read_moves::<2>("example.txt").unwrap(), 10, 1);
+        assert_eq!(tracks.len(), 10);
+        assert_eq!(tracks[0].trajectory.len(), 25);
+        assert_eq!(tracks[0].trajectory[4], [4, 0]);
+        assert_eq!(tracks[0].bounding_box(), ([0, 0], [5, 4]));
+        assert_eq!(tracks[1].distinct_positions(), 13);
+        assert_eq!(tracks[1].first_move, Some(2));
+        assert_eq!(tracks[4].first_move, Some(6));
+        assert_eq!(tracks[9].first_move, None);
+        assert_eq!(tracks[9].bounding_box(), ([0, 0], [0, 0]));
+        assert_eq!(
+            moves::<2>("R x"),
+            Err(RopeError::InvalidSteps {
+                line: 1,
+                steps: "x".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn bitmap_test() {
+        let mut dense = DenseBitmap::new([-2, -1], [3, 4]);
+        assert!(dense.insert([-2, -1]));
+        assert!(dense.insert([3, 4]));
+        assert!(!dense.insert([3, 4]));
+        assert!(dense.contains([-2, -1]));
+        assert!(!dense.contains([0, 0]));
+        assert!(!dense.contains([4, 0]));
+        assert_eq!(dense.len(), 2);
+
+        let mut chunked = ChunkedBitmap::new();
+        for pos in [[-1, -1], [0, 0], [63, 64], [-64, 1 << 20], [0, 0]] {
+            chunked.insert(pos);
+        }
+        assert_eq!(chunked.len(), 4);
+        assert!(chunked.contains([-64, 1 << 20]));
+        assert!(!chunked.contains([-1, 0]));
+        assert!(!chunked.contains([64, 63]));
+
+        assert!(matches!(
+            Bitmap::for_bounds([0, 0], [9, 9]),
+            Bitmap::Dense(_)
+        ));
+        assert!(matches!(
+            Bitmap::for_bounds([-1 << 20, 0], [1 << 20, 1 << 10]),
+            Bitmap::Chunked(_)
+        ));
+        let (min, max) = ([0, 0], [4095, 4095]);
+        assert!(matches!(
+            Bitmap::many_for_bounds(min, max, 4)[3],
+            Bitmap::Dense(_)
+        ));
+        assert!(matches!(
+            Bitmap::many_for_bounds(min, max, 10)[0],
+            Bitmap::Chunked(_)
+        ));
+
+        let moves = read_moves::<2>("input.txt").unwrap();
+        let counts = visited_counts(&moves, 10, 2);
+        let tracks = track(&moves, 10, 2);
+        assert!(counts
+            .iter()
+            .zip(tracks.iter())
+            .all(|(c, t)| *c == t.distinct_positions()));
+    }
 }
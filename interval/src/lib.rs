@@ -0,0 +1,318 @@
+use std::fmt;
+use std::ops::{Add, RangeInclusive, Sub};
+
+/// Integer types usable as interval bounds.
+pub trait Bound: Copy + Ord + Add<Output = Self> + Sub<Output = Self> {
+    const ZERO: Self;
+    const ONE: Self;
+
+    fn checked_add(self, other: Self) -> Option<Self>;
+    fn checked_sub(self, other: Self) -> Option<Self>;
+}
+
+macro_rules! impl_bound {
+    ($($t:ty),*) => {
+        $(impl Bound for $t {
+            const ZERO: Self = 0;
+            const ONE: Self = 1;
+
+            fn checked_add(self, other: Self) -> Option<Self> {
+                <$t>::checked_add(self, other)
+            }
+
+            fn checked_sub(self, other: Self) -> Option<Self> {
+                <$t>::checked_sub(self, other)
+            }
+        })*
+    };
+}
+
+impl_bound!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+/// An inclusive range of integers, `start..=end`, that is never empty.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Interval<T> {
+    start: T,
+    end: T,
+}
+
+impl<T: Bound> Interval<T> {
+    pub fn new(start: T, end: T) -> Self {
+        assert!(start <= end, "Interval start lies after its end");
+        Self { start, end }
+    }
+
+    pub fn start(&self) -> T {
+        self.start
+    }
+
+    pub fn end(&self) -> T {
+        self.end
+    }
+
+    /// Number of integers covered by this interval.
+    ///
+    /// Panics if that number doesn't fit into `T`, like it does for the
+    /// full range of a type. Use `checked_size` to handle that case.
+    pub fn size(&self) -> T {
+        self.checked_size()
+            .expect("Interval size overflows its bound type")
+    }
+
+    /// Number of integers covered by this interval, or `None` if that
+    /// number doesn't fit into `T`.
+    pub fn checked_size(&self) -> Option<T> {
+        self.end.checked_sub(self.start)?.checked_add(T::ONE)
+    }
+
+    pub fn contains_point(&self, value: T) -> bool {
+        self.start <= value && value <= self.end
+    }
+
+    /// Whether `other` lies completely within `self`.
+    pub fn contains(&self, other: &Self) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+
+    pub fn overlaps(&self, other: &Self) -> bool {
+        self.start <= other.end && other.start <= self.end
+    }
+
+    /// Whether both intervals overlap or sit right next to each other,
+    /// meaning their union is a single interval.
+    pub fn touches(&self, other: &Self) -> bool {
+        // The end that lies before the other start can't be the maximum of
+        // `T`, so adding one to it can't overflow.
+        self.overlaps(other)
+            || (self.end < other.start && self.end + T::ONE == other.start)
+            || (other.end < self.start && other.end + T::ONE == self.start)
+    }
+
+    pub fn intersection(&self, other: &Self) -> Option<Self> {
+        if self.overlaps(other) {
+            Some(Self {
+                start: self.start.max(other.start),
+                end: self.end.min(other.end),
+            })
+        } else {
+            None
+        }
+    }
+
+    /// The union of both intervals, or `None` if there is a gap between them.
+    pub fn union(&self, other: &Self) -> Option<Self> {
+        if self.touches(other) {
+            Some(Self {
+                start: self.start.min(other.start),
+                end: self.end.max(other.end),
+            })
+        } else {
+            None
+        }
+    }
+}
+
+impl<T: Bound> From<RangeInclusive<T>> for Interval<T> {
+    fn from(range: RangeInclusive<T>) -> Self {
+        let (start, end) = range.into_inner();
+        Self::new(start, end)
+    }
+}
+
+impl<T> From<Interval<T>> for RangeInclusive<T> {
+    fn from(interval: Interval<T>) -> Self {
+        interval.start..=interval.end
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Interval<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+/// A set of integers, stored as sorted intervals that neither overlap nor touch.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IntervalSet<T> {
+    intervals: Vec<Interval<T>>,
+}
+
+impl<T> Default for IntervalSet<T> {
+    fn default() -> Self {
+        Self { intervals: vec![] }
+    }
+}
+
+impl<T: Bound> IntervalSet<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            intervals: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Removes all intervals while keeping the allocation around for reuse.
+    pub fn clear(&mut self) {
+        self.intervals.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+
+    pub fn intervals(&self) -> &[Interval<T>] {
+        &self.intervals
+    }
+
+    pub fn insert(&mut self, interval: Interval<T>) {
+        let lo = self
+            .intervals
+            .partition_point(|i| i.end < interval.start && !i.touches(&interval));
+        let hi = self
+            .intervals
+            .partition_point(|i| i.start <= interval.end || i.touches(&interval));
+        let merged = self.intervals[lo..hi]
+            .iter()
+            .fold(interval, |acc, i| acc.union(i).unwrap());
+        self.intervals.splice(lo..hi, [merged]);
+    }
+
+    pub fn contains_point(&self, value: T) -> bool {
+        let i = self.intervals.partition_point(|i| i.end < value);
+        self.intervals
+            .get(i)
+            .is_some_and(|i| i.contains_point(value))
+    }
+
+    /// Total number of integers in the set.
+    ///
+    /// Panics if that number doesn't fit into `T`, see `checked_size`.
+    pub fn size(&self) -> T {
+        self.checked_size()
+            .expect("Interval set size overflows its bound type")
+    }
+
+    /// Total number of integers in the set, or `None` if that number
+    /// doesn't fit into `T`.
+    pub fn checked_size(&self) -> Option<T> {
+        self.intervals
+            .iter()
+            .try_fold(T::ZERO, |acc, i| acc.checked_add(i.checked_size()?))
+    }
+
+    /// The uncovered intervals between the first and the last interval of the set.
+    pub fn gaps(&self) -> impl Iterator<Item = Interval<T>> + '_ {
+        self.intervals
+            .windows(2)
+            .map(|w| Interval::new(w[0].end + T::ONE, w[1].start - T::ONE))
+    }
+
+    /// Restores the sorted, disjoint invariant after raw intervals were appended.
+    fn normalize(&mut self) {
+        self.intervals.sort_unstable_by_key(|i| i.start);
+        let mut len = 0;
+        for i in 0..self.intervals.len() {
+            let cur = self.intervals[i];
+            if len > 0 {
+                if let Some(merged) = self.intervals[len - 1].union(&cur) {
+                    self.intervals[len - 1] = merged;
+                    continue;
+                }
+            }
+            self.intervals[len] = cur;
+            len += 1;
+        }
+        self.intervals.truncate(len);
+    }
+}
+
+impl<T: Bound> Extend<Interval<T>> for IntervalSet<T> {
+    fn extend<I: IntoIterator<Item = Interval<T>>>(&mut self, iter: I) {
+        self.intervals.extend(iter);
+        self.normalize();
+    }
+}
+
+impl<T: Bound> FromIterator<Interval<T>> for IntervalSet<T> {
+    fn from_iter<I: IntoIterator<Item = Interval<T>>>(iter: I) -> Self {
+        let mut ret = Self::new();
+        ret.extend(iter);
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interval_ops() {
+        let a = Interval::new(2, 6);
+        let b = Interval::new(4, 8);
+        let c = Interval::new(9, 9);
+        assert!(a.overlaps(&b));
+        assert!(!a.contains(&b));
+        assert!(Interval::new(1, 9).contains(&b));
+        assert_eq!(a.intersection(&b), Some(Interval::new(4, 6)));
+        assert_eq!(a.intersection(&c), None);
+        assert_eq!(b.union(&c), Some(Interval::new(4, 9)));
+        assert_eq!(a.union(&c), None);
+        assert_eq!(a.size(), 5);
+    }
+
+    #[test]
+    fn set_merging() {
+        let mut set: IntervalSet<i64> = [(10, 12), (1, 3), (4, 5), (15, 20)]
+            .iter()
+            .map(|&(a, b)| Interval::new(a, b))
+            .collect();
+        assert_eq!(
+            set.intervals(),
+            [
+                Interval::new(1, 5),
+                Interval::new(10, 12),
+                Interval::new(15, 20)
+            ]
+        );
+        assert_eq!(
+            set.gaps().collect::<Vec<_>>(),
+            [Interval::new(6, 9), Interval::new(13, 14)]
+        );
+
+        set.insert(Interval::new(6, 14));
+        assert_eq!(set.intervals(), [Interval::new(1, 20)]);
+        assert_eq!(set.size(), 20);
+        assert!(set.contains_point(7));
+        assert!(!set.contains_point(21));
+    }
+
+    #[test]
+    fn extreme_bounds() {
+        let min = Interval::new(i32::MIN, i32::MIN);
+        let max = Interval::new(i32::MAX, i32::MAX);
+        assert!(!min.touches(&max) && !max.touches(&min));
+        assert_eq!(min.union(&max), None);
+        assert!(Interval::new(i32::MAX - 1, i32::MAX - 1).touches(&max));
+        assert_eq!(Interval::new(i32::MIN, i32::MAX).checked_size(), None);
+        assert_eq!(Interval::new(i32::MIN, -1).checked_size(), None);
+        assert_eq!(Interval::new(i32::MIN, -2).checked_size(), Some(i32::MAX));
+        assert_eq!(Interval::new(0, u8::MAX).checked_size(), None);
+
+        let mut set: IntervalSet<i32> = [max, min].into_iter().collect();
+        set.insert(Interval::new(i32::MIN + 1, 0));
+        assert_eq!(
+            set.intervals(),
+            [
+                Interval::new(i32::MIN, 0),
+                Interval::new(i32::MAX, i32::MAX)
+            ]
+        );
+        assert_eq!(set.checked_size(), None);
+        set.clear();
+        set.extend([Interval::new(0, 10), Interval::new(-10, -1)]);
+        assert_eq!(set.checked_size(), Some(21));
+    }
+}